@@ -0,0 +1,162 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+pub const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Runtime settings, read once from the environment at startup.
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub max_body_bytes: usize,
+    pub max_upload_bytes: usize,
+    pub request_timeout: Duration,
+    pub db_max_connections: u32,
+    pub db_acquire_timeout: Duration,
+    pub db_idle_timeout: Duration,
+    // `None` allows any origin outside production
+    pub cors_origins: Option<Vec<String>>,
+    pub production: bool,
+    pub rate_limit_per_sec: f64,
+    pub rate_limit_burst: f64,
+    pub pokeapi_url: String,
+    pub poke_cache_ttl: Duration,
+    pub poke_miss_ttl: Duration,
+    pub tweet_max_len: usize,
+    pub room_capacity: usize,
+    pub ws_ping_interval: Duration,
+    pub totals_interval: Duration,
+    // NICE_FORBIDDEN="ab,cd,pq,xy"
+    pub nice_forbidden: Vec<String>,
+    pub nice_vowels: String,
+    // when set, the day7 recipe cookie must carry an HMAC made with it
+    pub recipe_cookie_key: Option<String>,
+    // ROOM_TOKENS="1=secret,2=another"
+    pub room_tokens: HashMap<usize, String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self::from_vars(|_| None)
+    }
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let secs_or = |name, default| Duration::from_secs(parse_or(&var, name, default));
+
+        Self {
+            max_body_bytes: parse_or(&var, "MAX_BODY_BYTES", 8 * 1024 * 1024),
+            max_upload_bytes: parse_or(&var, "MAX_UPLOAD_BYTES", 32 * 1024 * 1024),
+            request_timeout: secs_or("REQUEST_TIMEOUT_SECS", 30),
+            db_max_connections: parse_or(&var, "DB_MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS),
+            db_acquire_timeout: secs_or(
+                "DB_ACQUIRE_TIMEOUT_SECS",
+                DEFAULT_ACQUIRE_TIMEOUT.as_secs(),
+            ),
+            db_idle_timeout: secs_or("DB_IDLE_TIMEOUT_SECS", DEFAULT_IDLE_TIMEOUT.as_secs()),
+            cors_origins: var("CORS_ALLOWED_ORIGINS").map(|origins| {
+                origins
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(String::from)
+                    .collect()
+            }),
+            production: ["APP_ENV", "SHUTTLE_ENV"]
+                .iter()
+                .any(|name| matches!(var(name).as_deref(), Some("production" | "deployment"))),
+            rate_limit_per_sec: parse_or(&var, "RATE_LIMIT_PER_SEC", 50.0),
+            rate_limit_burst: parse_or(&var, "RATE_LIMIT_BURST", 100.0),
+            pokeapi_url: var("POKEAPI_URL")
+                .unwrap_or_else(|| "https://pokeapi.co/api/v2".to_string()),
+            poke_cache_ttl: secs_or("POKE_CACHE_TTL_SECS", 10 * 60),
+            poke_miss_ttl: secs_or("POKE_MISS_TTL_SECS", 60),
+            tweet_max_len: parse_or(&var, "TWEET_MAX_LEN", 128),
+            room_capacity: parse_or(&var, "ROOM_CAPACITY", 1024),
+            ws_ping_interval: secs_or("WS_PING_INTERVAL_SECS", 30),
+            totals_interval: secs_or("TOTALS_INTERVAL_SECS", 5),
+            nice_forbidden: var("NICE_FORBIDDEN")
+                .map(|pairs| {
+                    pairs
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|pair| !pair.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_else(|| ["ab", "cd", "pq", "xy"].map(String::from).to_vec()),
+            nice_vowels: var("NICE_VOWELS").unwrap_or_else(|| "aeiouy".to_string()),
+            recipe_cookie_key: var("RECIPE_COOKIE_KEY").filter(|key| !key.is_empty()),
+            room_tokens: var("ROOM_TOKENS")
+                .map(|tokens| {
+                    tokens
+                        .split(',')
+                        .filter_map(|entry| {
+                            let (room, token) = entry.split_once('=')?;
+                            Some((room.trim().parse().ok()?, token.trim().to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// unset or unparsable values fall back to the default
+fn parse_or<T: FromStr>(var: &impl Fn(&str) -> Option<String>, name: &str, default: T) -> T {
+    var(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> AppConfig {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        AppConfig::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let config = config(&[]);
+        assert_eq!(config.max_body_bytes, 8 * 1024 * 1024);
+        assert_eq!(config.db_max_connections, DEFAULT_MAX_CONNECTIONS);
+        assert_eq!(config.db_acquire_timeout, DEFAULT_ACQUIRE_TIMEOUT);
+        assert_eq!(config.db_idle_timeout, DEFAULT_IDLE_TIMEOUT);
+        assert_eq!(config.cors_origins, None);
+        assert!(!config.production);
+        assert_eq!(config.poke_cache_ttl, Duration::from_secs(600));
+        assert_eq!(config.tweet_max_len, 128);
+        assert!(config.room_tokens.is_empty());
+    }
+
+    #[test]
+    fn overrides() {
+        let config = config(&[
+            ("MAX_BODY_BYTES", "1024"),
+            ("DB_MAX_CONNECTIONS", "not a number"),
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://a.example, https://b.example,",
+            ),
+            ("SHUTTLE_ENV", "production"),
+            ("POKE_CACHE_TTL_SECS", "5"),
+            ("TWEET_MAX_LEN", "280"),
+            ("ROOM_TOKENS", "1=secret, 2 = other"),
+        ]);
+        assert_eq!(config.max_body_bytes, 1024);
+        assert_eq!(config.db_max_connections, DEFAULT_MAX_CONNECTIONS);
+        assert_eq!(
+            config.cors_origins,
+            Some(vec!["https://a.example".into(), "https://b.example".into()])
+        );
+        assert!(config.production);
+        assert_eq!(config.poke_cache_ttl, Duration::from_secs(5));
+        assert_eq!(config.tweet_max_len, 280);
+        assert_eq!(config.room_tokens[&2], "other");
+    }
+}
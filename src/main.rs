@@ -1,49 +1,144 @@
 use std::{
+    borrow::Cow,
     cmp::Reverse,
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeMap, BinaryHeap, HashMap, VecDeque},
+    convert::Infallible,
     fs,
-    io::Cursor,
+    io::{Cursor, Read},
+    net::SocketAddr,
+    path::Component,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc, Mutex, RwLock,
+        Arc, Mutex, OnceLock, RwLock,
     },
+    time::{Duration, Instant},
 };
 
+use askama::Template;
 use axum::{
+    body::Body,
     extract::{
-        ws::{Message, WebSocket},
-        Multipart, Path, Query, State, WebSocketUpgrade,
+        rejection::JsonRejection,
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        DefaultBodyLimit, FromRequest, Multipart, Path, Query, State, WebSocketUpgrade,
     },
-    http::StatusCode,
-    response::{IntoResponse, Response, Result},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{Html, IntoResponse, Response, Result},
     routing::{get, post},
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
 use base64::Engine;
 use bytes::{Buf as _, Bytes};
-use country_boundaries::LatLon;
-use dms_coordinates::DMS;
+use country_boundaries::{CountryBoundaries, LatLon};
 use euclid::default::*;
 use euclid::point3;
 use futures_util::{future::Either, stream_select, SinkExt as _, StreamExt as _};
+use hmac::Mac as _;
 use ordered_float::OrderedFloat;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
 use shuttle_runtime::CustomError;
-use sqlx::{PgPool, QueryBuilder};
+use sqlx::{postgres::PgPoolOptions, PgPool, QueryBuilder};
+use time::format_description::well_known::Rfc3339;
+use time_tz::OffsetDateTimeExt as _;
 use tokio::sync::broadcast::{Receiver, Sender};
-use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_util::sync::CancellationToken;
+use tower_http::{
+    compression::{predicate::DefaultPredicate, CompressionLayer, Predicate as _},
+    cors::{AllowOrigin, Any, CorsLayer},
+    limit::RequestBodyLimitLayer,
+};
+use unicode_segmentation::UnicodeSegmentation as _;
+
+mod config;
+mod logging;
+mod metrics;
+mod openapi;
+mod ratelimit;
+
+use config::AppConfig;
+use ratelimit::TokenBucket;
+
+enum AppError {
+    BadRequest(anyhow::Error),
+    // what wasn't found, plus the fields naming it, e.g. `{"key": ..}`
+    NotFound(String, serde_json::Value),
+    UnsupportedMediaType(anyhow::Error),
+    PayloadTooLarge,
+    // well-formed, but nothing can be made of it
+    Unprocessable(anyhow::Error),
+    Json(JsonRejection),
+    Upstream(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    fn bad_request(err: impl Into<anyhow::Error>) -> Self {
+        Self::BadRequest(err.into())
+    }
+
+    fn not_found(msg: impl Into<String>, fields: serde_json::Value) -> Self {
+        Self::NotFound(msg.into(), fields)
+    }
+
+    fn unsupported_media_type(err: impl Into<anyhow::Error>) -> Self {
+        Self::UnsupportedMediaType(err.into())
+    }
 
-struct AppError(anyhow::Error);
+    fn unprocessable(err: impl Into<anyhow::Error>) -> Self {
+        Self::Unprocessable(err.into())
+    }
+
+    fn upstream(err: impl Into<anyhow::Error>) -> Self {
+        Self::Upstream(err.into())
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(..) => StatusCode::NOT_FOUND,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Json(rejection) => rejection.status(),
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+        let status = self.status();
+        // extra fields go next to "error" and "status"
+        let (msg, extra) = match self {
+            AppError::BadRequest(err)
+            | AppError::UnsupportedMediaType(err)
+            | AppError::Unprocessable(err)
+            | AppError::Upstream(err)
+            | AppError::Internal(err) => (err.to_string(), json!({})),
+            AppError::NotFound(msg, fields) => (msg, fields),
+            AppError::PayloadTooLarge => ("payload too large".to_string(), json!({})),
+            AppError::Json(rejection) => {
+                let msg = match rejection {
+                    JsonRejection::MissingJsonContentType(_) => {
+                        "expected `Content-Type: application/json`"
+                    }
+                    JsonRejection::JsonSyntaxError(_) => "body is not valid JSON",
+                    JsonRejection::JsonDataError(_) => "JSON does not match the expected shape",
+                    _ => "failed to read the request body",
+                };
+                (msg.to_string(), json!({ "detail": rejection.body_text() }))
+            }
+        };
+
+        let mut body = json!({"error": msg, "status": status.as_u16()});
+        if let serde_json::Value::Object(extra) = extra {
+            body.as_object_mut().unwrap().extend(extra);
+        }
+        (status, Json(body)).into_response()
     }
 }
 
@@ -52,7 +147,27 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self(err.into())
+        Self::Internal(err.into())
+    }
+}
+
+/// Like `Json`, but rejections are reported as `AppError`s saying whether the
+/// content type, the syntax or the shape of the body was wrong.
+struct AppJson<T>(T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for AppJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: axum::extract::Request, state: &S) -> Result<Self, AppError> {
+        let Json(value) = Json::from_request(req, state)
+            .await
+            .map_err(AppError::Json)?;
+        Ok(Self(value))
     }
 }
 
@@ -60,17 +175,41 @@ async fn hello_world() -> &'static str {
     "Hello, world!"
 }
 
+async fn not_found(uri: Uri) -> AppError {
+    AppError::not_found("no such route", json!({ "path": uri.path() }))
+}
+
 async fn error() -> impl IntoResponse {
     (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
 }
 
-async fn day1(Path(nums): Path<String>) -> String {
-    let val = nums
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn health(State(pool): State<Pool>) -> impl IntoResponse {
+    let check = sqlx::query("SELECT 1").execute(&pool.pool);
+
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await {
+        Ok(Ok(_)) => (StatusCode::OK, Json(json!({ "status": "ok" }))),
+        _ => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "degraded" })),
+        ),
+    }
+}
+
+fn sled_id(nums: &[i64]) -> Option<i64> {
+    nums.iter().fold(0, |a, b| a ^ b).checked_pow(3)
+}
+
+async fn day1(Path(nums): Path<String>) -> Result<String, AppError> {
+    let nums = nums
         .split('/')
-        .map(|num| num.parse::<i64>().unwrap())
-        .fold(0, |a, b| a ^ b)
-        .pow(3);
-    format!("{val}")
+        .map(|num| num.parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AppError::bad_request)?;
+    let val = sled_id(&nums)
+        .ok_or_else(|| AppError::bad_request(anyhow::anyhow!("sled id overflows")))?;
+    Ok(format!("{val}"))
 }
 
 #[derive(Deserialize)]
@@ -92,12 +231,68 @@ struct Reindeer {
     candies: i64,
 }
 
-async fn day4_task1(Json(payload): Json<Vec<Reindeer>>) -> String {
+async fn day4_task1(AppJson(payload): AppJson<Vec<Reindeer>>) -> String {
     let sum = payload.iter().fold(0, |a, b| a + b.strength);
     format!("{sum}")
 }
 
-async fn day4_task2(Json(payload): Json<Vec<Reindeer>>) -> impl IntoResponse {
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ContestField {
+    Speed,
+    Height,
+    SnowMagicPower,
+    Candies,
+    Strength,
+}
+
+impl ContestField {
+    fn winner(self, payload: &[Reindeer]) -> Option<(&Reindeer, serde_json::Value)> {
+        let by_key = |key: fn(&Reindeer) -> i64| {
+            let r = payload.iter().max_by_key(|r| key(r))?;
+            Some((r, json!(key(r))))
+        };
+
+        match self {
+            ContestField::Speed => {
+                let r = payload
+                    .iter()
+                    .max_by(|a, b| a.speed.partial_cmp(&b.speed).unwrap())?;
+                Some((r, json!(r.speed)))
+            }
+            ContestField::Height => by_key(|r| r.height),
+            ContestField::SnowMagicPower => by_key(|r| r.snow_magic_power),
+            ContestField::Candies => by_key(|r| r.candies),
+            ContestField::Strength => by_key(|r| r.strength),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ContestQuery {
+    by: Option<ContestField>,
+}
+
+async fn day4_task2(
+    Query(query): Query<ContestQuery>,
+    AppJson(payload): AppJson<Vec<Reindeer>>,
+) -> Result<Response, AppError> {
+    // height is an integer, so speed is the only stat that can be non-finite
+    if payload.iter().any(|r| !r.speed.is_finite()) {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "invalid reindeer stats"
+        )));
+    }
+    // every category below needs at least one contestant
+    if payload.is_empty() {
+        return Err(AppError::bad_request(anyhow::anyhow!("no reindeer given")));
+    }
+
+    if let Some(by) = query.by {
+        let (winner, value) = by.winner(&payload).unwrap();
+        return Ok(Json(json!({"winner": winner.name, "value": value})).into_response());
+    }
+
     let fastest = payload
         .iter()
         .max_by(|a, b| a.speed.partial_cmp(&b.speed).unwrap())
@@ -115,12 +310,13 @@ async fn day4_task2(Json(payload): Json<Vec<Reindeer>>) -> impl IntoResponse {
         .max_by(|a, b| a.candies.cmp(&b.candies))
         .unwrap();
 
-    Json(json!({
+    Ok(Json(json!({
         "fastest": format!("Speeding past the finish line with a strength of {} is {}", fastest.strength, fastest.name),
         "tallest": format!("{} is standing tall with his {} cm wide antlers", tallest.name, tallest.antler_width),
         "magician": format!("{} could blast you away with a snow magic power of {}", magician.name, magician.snow_magic_power),
         "consumer": format!("{} ate lots of candies, but also some {}", consumer.name, consumer.favorite_food),
     }))
+    .into_response())
 }
 
 #[derive(Deserialize)]
@@ -128,34 +324,144 @@ struct Pagination {
     offset: Option<usize>,
     limit: Option<usize>,
     split: Option<usize>,
+    #[serde(default)]
+    format: NamesFormat,
 }
 
-async fn day5(pagination: Query<Pagination>, Json(names): Json<Vec<String>>) -> impl IntoResponse {
-    let offset = pagination.offset.unwrap_or(0);
-    let names = if let Some(limit) = pagination.limit {
-        names[offset..(offset + limit).min(names.len())].to_vec()
-    } else {
-        names[offset..].to_vec()
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum NamesFormat {
+    #[default]
+    Json,
+    Ndjson,
+}
+
+// one JSON value per line, serialized lazily as the body is polled
+fn ndjson_body(lines: impl Iterator<Item = serde_json::Value> + Send + 'static) -> Response {
+    let lines = lines.map(|line| Ok::<_, Infallible>(format!("{line}\n")));
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(futures_util::stream::iter(lines)),
+    )
+        .into_response()
+}
+
+// the part of a `len` long list that `offset` and `limit` select; offsets
+// past the end select nothing rather than failing
+fn page_window(offset: Option<usize>, limit: Option<usize>, len: usize) -> std::ops::Range<usize> {
+    let start = offset.unwrap_or(0).min(len);
+    let end = limit.map_or(len, |limit| start.saturating_add(limit).min(len));
+    start..end
+}
+
+fn split_error() -> AppError {
+    AppError::bad_request(anyhow::anyhow!("split must be >= 1"))
+}
+
+async fn day5(
+    pagination: Query<Pagination>,
+    AppJson(names): AppJson<Vec<String>>,
+) -> Result<Response, AppError> {
+    if pagination.split == Some(0) {
+        return Err(split_error());
+    }
+
+    let names = names[page_window(pagination.offset, pagination.limit, names.len())].to_vec();
+
+    let resp = match (pagination.format, pagination.split) {
+        (NamesFormat::Json, Some(split)) => {
+            Json(json!(names.chunks(split).collect::<Vec<_>>())).into_response()
+        }
+        (NamesFormat::Json, None) => Json(json!(names)).into_response(),
+        (NamesFormat::Ndjson, Some(split)) => {
+            let mut names = names.into_iter();
+            ndjson_body(std::iter::from_fn(move || {
+                let chunk = names.by_ref().take(split).collect::<Vec<_>>();
+                (!chunk.is_empty()).then(|| json!(chunk))
+            }))
+        }
+        (NamesFormat::Ndjson, None) => ndjson_body(names.into_iter().map(|name| json!(name))),
     };
+    Ok(resp)
+}
 
-    if let Some(split) = pagination.split {
-        Json(json!(names.chunks(split).collect::<Vec<_>>()))
-    } else {
-        Json(json!(names))
+#[derive(Deserialize)]
+struct ExplainQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+    split: Option<usize>,
+    len: usize,
+}
+
+async fn day5_explain(Query(query): Query<ExplainQuery>) -> Result<impl IntoResponse, AppError> {
+    if query.split == Some(0) {
+        return Err(split_error());
     }
+
+    let window = page_window(query.offset, query.limit, query.len);
+    Ok(Json(json!({
+        "offset": query.offset,
+        "limit": query.limit,
+        "split": query.split,
+        "effective_start": window.start,
+        "effective_end": window.end,
+    })))
 }
 
-async fn day6(body: String) -> impl IntoResponse {
-    let count =
-        |s: &str, pat: &str| -> usize { (0..s.len()).filter(|i| s[*i..].starts_with(pat)).count() };
+const ELF_ON_A_SHELF: &[u8] = b"elf on a shelf";
 
-    let elf_on_a_shelf = count(&body, "elf on a shelf");
+// counts over a sliding window of the last few bytes, so a body of any size
+// is scanned once without ever being buffered whole
+#[derive(Default)]
+struct ElfCounter {
+    window: [u8; ELF_ON_A_SHELF.len()],
+    elf: usize,
+    shelf: usize,
+    elf_on_a_shelf: usize,
+}
 
-    Json(json!({
-        "elf": count(&body, "elf"),
-        "elf on a shelf": elf_on_a_shelf,
-        "shelf with no elf on it": count(&body, "shelf") - elf_on_a_shelf,
-    }))
+impl ElfCounter {
+    fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.window.copy_within(1.., 0);
+            *self.window.last_mut().unwrap() = b;
+
+            if self.window.ends_with(b"elf") {
+                self.elf += 1;
+            }
+            if self.window.ends_with(b"shelf") {
+                self.shelf += 1;
+            }
+            if self.window == ELF_ON_A_SHELF {
+                self.elf_on_a_shelf += 1;
+            }
+        }
+    }
+}
+
+// extractors like `Bytes` turn the body limit into a 413 on their own, but
+// handlers streaming the body only see it as a read error
+fn body_error(err: axum::Error) -> AppError {
+    let err = err.into_inner();
+    if err.is::<http_body_util::LengthLimitError>() {
+        AppError::PayloadTooLarge
+    } else {
+        AppError::BadRequest(anyhow::anyhow!(err))
+    }
+}
+
+async fn day6(body: Body) -> Result<impl IntoResponse, AppError> {
+    let mut counter = ElfCounter::default();
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        counter.feed(&chunk.map_err(body_error)?);
+    }
+
+    Ok(Json(json!({
+        "elf": counter.elf,
+        "elf on a shelf": counter.elf_on_a_shelf,
+        "shelf with no elf on it": counter.shelf - counter.elf_on_a_shelf,
+    })))
 }
 
 fn get_value_from_cookie<T: DeserializeOwned>(jar: &CookieJar, name: &str) -> Option<T> {
@@ -165,18 +471,72 @@ fn get_value_from_cookie<T: DeserializeOwned>(jar: &CookieJar, name: &str) -> Op
     serde_json::from_str(&decoded).ok()
 }
 
-async fn day7_task1(jar: CookieJar) -> impl IntoResponse {
-    let input = get_value_from_cookie::<serde_json::Value>(&jar, "recipe").unwrap();
-    Json(input)
+/// Reads cookies shaped `base64(payload).base64(hmac_sha256(payload))` when a
+/// key is configured, and plain `base64(payload)` ones otherwise.
+#[derive(Clone)]
+struct CookieSigner {
+    key: Option<Arc<[u8]>>,
+}
+
+impl CookieSigner {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            key: config
+                .recipe_cookie_key
+                .as_ref()
+                .map(|key| key.as_bytes().into()),
+        }
+    }
+
+    fn get_signed_value_from_cookie<T: DeserializeOwned>(
+        &self,
+        jar: &CookieJar,
+        name: &str,
+    ) -> Result<T, AppError> {
+        let bad_cookie = |msg: &str| AppError::bad_request(anyhow::anyhow!("{name} cookie: {msg}"));
+
+        let Some(key) = &self.key else {
+            return get_value_from_cookie(jar, name)
+                .ok_or_else(|| bad_cookie("missing or invalid"));
+        };
+
+        let value = jar.get(name).ok_or_else(|| bad_cookie("missing"))?.value();
+        let (payload, signature) = value
+            .split_once('.')
+            .ok_or_else(|| bad_cookie("not signed"))?;
+        let decode = |s| base64::prelude::BASE64_STANDARD.decode(s);
+        let (Ok(payload), Ok(signature)) = (decode(payload), decode(signature)) else {
+            return Err(bad_cookie("invalid base64"));
+        };
+
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+        mac.update(&payload);
+        mac.verify_slice(&signature)
+            .map_err(|_| bad_cookie("bad signature"))?;
+
+        serde_json::from_slice(&payload).map_err(AppError::bad_request)
+    }
 }
 
-async fn day7_task2_3(jar: CookieJar) -> impl IntoResponse {
-    let mut input =
-        get_value_from_cookie::<HashMap<String, HashMap<String, i64>>>(&jar, "recipe").unwrap();
+async fn day7_task1(
+    State(signer): State<CookieSigner>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    let input = signer.get_signed_value_from_cookie::<serde_json::Value>(&jar, "recipe")?;
+    Ok(Json(input))
+}
 
-    let recipe = std::mem::take(input.get_mut("recipe").unwrap());
-    let mut pantry = std::mem::take(input.get_mut("pantry").unwrap());
+// ordered maps keep the returned pantry stable across runs
+#[derive(Deserialize)]
+struct BakeRequest {
+    recipe: BTreeMap<String, i64>,
+    pantry: BTreeMap<String, i64>,
+}
 
+fn bake(
+    recipe: BTreeMap<String, i64>,
+    mut pantry: BTreeMap<String, i64>,
+) -> (i64, BTreeMap<String, i64>) {
     let mut cookies = i64::MAX;
 
     for (ingred, amount) in &recipe {
@@ -191,35 +551,249 @@ async fn day7_task2_3(jar: CookieJar) -> impl IntoResponse {
         }
     }
 
+    (cookies, pantry)
+}
+
+async fn day7_task2_3(
+    State(signer): State<CookieSigner>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    let input = signer.get_signed_value_from_cookie::<BakeRequest>(&jar, "recipe")?;
+    let (cookies, pantry) = bake(input.recipe, input.pantry);
+    Ok(Json(json!({"cookies": cookies, "pantry": pantry})))
+}
+
+async fn day7_bake_json(AppJson(input): AppJson<BakeRequest>) -> impl IntoResponse {
+    let (cookies, pantry) = bake(input.recipe, input.pantry);
     Json(json!({"cookies": cookies, "pantry": pantry}))
 }
 
-async fn pokeapi(id: u64) -> Result<HashMap<String, serde_json::Value>, AppError> {
-    Ok(
-        reqwest::get(format!("https://pokeapi.co/api/v2/pokemon/{id}/"))
-            .await?
-            .json::<HashMap<String, serde_json::Value>>()
-            .await?,
-    )
+#[derive(Deserialize)]
+struct NamedRecipe {
+    name: String,
+    ingredients: BTreeMap<String, i64>,
+}
+
+#[derive(Deserialize)]
+struct BakeBatchRequest {
+    pantry: BTreeMap<String, i64>,
+    recipes: Vec<NamedRecipe>,
+}
+
+// every recipe is baked against the full pantry, not what the previous one left
+async fn day7_bake_batch(AppJson(input): AppJson<BakeBatchRequest>) -> impl IntoResponse {
+    let results = input
+        .recipes
+        .into_iter()
+        .map(|recipe| {
+            let (cookies, _) = bake(recipe.ingredients, input.pantry.clone());
+            json!({"name": recipe.name, "cookies": cookies})
+        })
+        .collect::<Vec<_>>();
+    Json(results)
+}
+
+const POKEAPI_TIMEOUT: Duration = Duration::from_secs(5);
+const POKEAPI_RETRIES: u32 = 3;
+const POKEAPI_BACKOFF: Duration = Duration::from_millis(100);
+
+type Pokemon = HashMap<String, serde_json::Value>;
+
+#[derive(Clone)]
+enum CacheEntry {
+    Hit(Pokemon),
+    Miss,
+}
+
+fn pokemon_not_found(id: u64) -> AppError {
+    AppError::not_found("pokemon not found", json!({ "id": id }))
+}
+
+struct PokeApi {
+    client: reqwest::Client,
+    base_url: String,
+    cache: Mutex<HashMap<u64, (Instant, CacheEntry)>>,
+    hit_ttl: Duration,
+    miss_ttl: Duration,
+}
+
+impl PokeApi {
+    fn new(config: &AppConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(POKEAPI_TIMEOUT)
+            .timeout(POKEAPI_TIMEOUT)
+            .build()
+            .unwrap();
+        Self {
+            client,
+            base_url: config.pokeapi_url.trim_end_matches('/').to_string(),
+            cache: Default::default(),
+            hit_ttl: config.poke_cache_ttl,
+            miss_ttl: config.poke_miss_ttl,
+        }
+    }
+
+    fn cached(&self, id: u64) -> Option<CacheEntry> {
+        let mut cache = self.cache.lock().unwrap();
+        let (expires, entry) = cache.get(&id)?;
+        if *expires > Instant::now() {
+            return Some(entry.clone());
+        }
+        cache.remove(&id);
+        None
+    }
+
+    async fn pokemon(&self, id: u64) -> Result<Pokemon, AppError> {
+        let entry = match self.cached(id) {
+            Some(entry) => entry,
+            None => {
+                // unknown ids are cached too, just for less time than hits
+                let (entry, ttl) = match self.fetch(id).await {
+                    Ok(pokemon) => (CacheEntry::Hit(pokemon), self.hit_ttl),
+                    Err(AppError::NotFound(..)) => (CacheEntry::Miss, self.miss_ttl),
+                    Err(err) => return Err(err),
+                };
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(id, (Instant::now() + ttl, entry.clone()));
+                entry
+            }
+        };
+
+        match entry {
+            CacheEntry::Hit(pokemon) => Ok(pokemon),
+            CacheEntry::Miss => Err(pokemon_not_found(id)),
+        }
+    }
+
+    async fn fetch(&self, id: u64) -> Result<Pokemon, AppError> {
+        let url = format!("{}/pokemon/{id}/", self.base_url);
+        let mut attempt = 0;
+
+        loop {
+            // only timeouts and 5xx are worth retrying; anything else won't change
+            let err = match self.client.get(&url).send().await {
+                Ok(resp) if resp.status() == reqwest::StatusCode::NOT_FOUND => {
+                    return Err(pokemon_not_found(id))
+                }
+                Ok(resp) if resp.status().is_server_error() => {
+                    anyhow::anyhow!("pokeapi returned {}", resp.status())
+                }
+                Ok(resp) => {
+                    return resp
+                        .error_for_status()
+                        .map_err(AppError::upstream)?
+                        .json()
+                        .await
+                        .map_err(AppError::upstream)
+                }
+                Err(err) if err.is_timeout() || err.is_connect() => err.into(),
+                Err(err) => return Err(AppError::upstream(err)),
+            };
+
+            if attempt == POKEAPI_RETRIES {
+                return Err(AppError::upstream(err));
+            }
+            tokio::time::sleep(POKEAPI_BACKOFF * 2u32.pow(attempt)).await;
+            attempt += 1;
+        }
+    }
 }
 
-async fn day8_task1(Path(id): Path<u64>) -> Result<String> {
-    let pokemon = pokeapi(id).await?;
+#[tracing::instrument(skip_all, fields(id = id))]
+async fn day8_task1(State(pokeapi): State<Arc<PokeApi>>, Path(id): Path<u64>) -> Result<String> {
+    let pokemon = pokeapi.pokemon(id).await?;
     let weight = pokemon.get("weight").unwrap().as_u64().unwrap();
     Ok(format!("{}", weight as f64 / 10.0))
 }
 
-async fn day8_task2(Path(id): Path<u64>) -> Result<String> {
-    let pokemon = pokeapi(id).await?;
+#[derive(Deserialize)]
+struct DropParams {
+    height: Option<f64>,
+    gravity: Option<f64>,
+}
+
+fn positive(name: &str, value: Option<f64>, default: f64) -> Result<f64, AppError> {
+    match value {
+        None => Ok(default),
+        Some(v) if v.is_finite() && v > 0.0 => Ok(v),
+        Some(v) => Err(AppError::bad_request(anyhow::anyhow!(
+            "{name} must be a positive number, got {v}"
+        ))),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(id = id))]
+async fn day8_task2(
+    State(pokeapi): State<Arc<PokeApi>>,
+    Path(id): Path<u64>,
+    Query(params): Query<DropParams>,
+) -> Result<String> {
+    let h = positive("height", params.height, 10.0)?;
+    let g = positive("gravity", params.gravity, 9.825)?;
+
+    let pokemon = pokeapi.pokemon(id).await?;
     let weight = pokemon.get("weight").unwrap().as_u64().unwrap();
-    let h = 10.0_f64;
-    let g = 9.825;
     let v = (2.0 * g * h).sqrt();
     let f = weight as f64 / 10.0 * v;
     Ok(format!("{f:.12}"))
 }
 
-async fn day11_task2(mut multipart: Multipart) -> Result<String, AppError> {
+#[tracing::instrument(skip_all, fields(id = id))]
+async fn day8_info(
+    State(pokeapi): State<Arc<PokeApi>>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let pokemon = pokeapi.pokemon(id).await?;
+    let field = |name: &str| pokemon.get(name).filter(|v| !v.is_null());
+
+    // types look like [{"slot": 1, "type": {"name": "electric", ...}}, ...]
+    let types = field("types")
+        .and_then(|types| types.as_array())
+        .map(|types| {
+            types
+                .iter()
+                .filter_map(|t| t.pointer("/type/name")?.as_str())
+                .collect::<Vec<_>>()
+        });
+
+    Ok(Json(json!({
+        "id": id,
+        "name": field("name").and_then(|v| v.as_str()),
+        "weight_kg": field("weight").and_then(|v| v.as_u64()).map(|w| w as f64 / 10.0),
+        "types": types,
+        "height": field("height").and_then(|v| v.as_u64()),
+    })))
+}
+
+const POKEAPI_CONCURRENCY: usize = 8;
+
+#[tracing::instrument(skip_all, fields(ids = ids.len()))]
+async fn day8_weights(
+    State(pokeapi): State<Arc<PokeApi>>,
+    AppJson(ids): AppJson<Vec<u64>>,
+) -> Json<HashMap<u64, Option<f64>>> {
+    // a failed lookup only nulls out its own id
+    let pokeapi = &pokeapi;
+    let weights = futures_util::stream::iter(ids)
+        .map(|id| async move {
+            let weight = pokeapi
+                .pokemon(id)
+                .await
+                .ok()
+                .and_then(|pokemon| pokemon.get("weight")?.as_u64())
+                .map(|weight| weight as f64 / 10.0);
+            (id, weight)
+        })
+        .buffer_unordered(POKEAPI_CONCURRENCY)
+        .collect()
+        .await;
+
+    Json(weights)
+}
+
+async fn decode_image(mut multipart: Multipart) -> Result<image::RgbaImage, AppError> {
     while let Some(field) = multipart.next_field().await? {
         if field.name() != Some("image") {
             continue;
@@ -229,57 +803,94 @@ async fn day11_task2(mut multipart: Multipart) -> Result<String, AppError> {
         let mut reader = image::io::Reader::new(Cursor::new(bytes));
         reader.set_format(image::ImageFormat::Png);
 
-        let image = reader.decode()?;
-        let image = image
-            .as_rgb8()
-            .ok_or_else(|| anyhow::anyhow!("unsupported color format"))?;
-
-        let mut red_pixels = 0;
-        for y in 0..image.height() {
-            for x in 0..image.width() {
-                let pixel = image.get_pixel(x, y);
-                let r = pixel[0] as u32;
-                let g = pixel[1] as u32;
-                let b = pixel[2] as u32;
-
-                if r > g + b {
-                    red_pixels += 1;
-                }
-            }
-        }
-
-        return Ok(format!("{red_pixels}"));
+        let image = reader.decode().map_err(AppError::unsupported_media_type)?;
+        return Ok(image.into_rgba8());
     }
 
-    Err(anyhow::anyhow!("no image found"))?
+    Err(AppError::bad_request(anyhow::anyhow!(
+        "no image field found"
+    )))
 }
 
-async fn day12_task1_post(State(state): State<Arc<RwLock<AppState>>>, Path(key): Path<String>) {
-    let mut lock = state.write().unwrap();
-    lock.day12.insert(key, time::Instant::now());
+// fully transparent pixels are invisible, so they never count as red
+fn is_red(&image::Rgba([r, g, b, a]): &image::Rgba<u8>) -> bool {
+    a > 0 && r as u32 > g as u32 + b as u32
 }
 
-async fn day12_task1_get(
-    State(state): State<Arc<RwLock<AppState>>>,
+async fn day11_task2(multipart: Multipart) -> Result<String, AppError> {
+    let image = decode_image(multipart).await?;
+    let red_pixels = image.pixels().filter(|p| is_red(p)).count();
+    Ok(format!("{red_pixels}"))
+}
+
+#[derive(Serialize)]
+struct RedBox {
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+    count: usize,
+}
+
+async fn day11_red_bbox(multipart: Multipart) -> Result<Json<Option<RedBox>>, AppError> {
+    let image = decode_image(multipart).await?;
+
+    let mut bbox: Option<RedBox> = None;
+    for (x, y, _) in image.enumerate_pixels().filter(|(_, _, p)| is_red(p)) {
+        let b = bbox.get_or_insert(RedBox {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+            count: 0,
+        });
+        b.min_x = b.min_x.min(x);
+        b.min_y = b.min_y.min(y);
+        b.max_x = b.max_x.max(x);
+        b.max_y = b.max_y.max(y);
+        b.count += 1;
+    }
+
+    Ok(Json(bbox))
+}
+
+async fn day12_task1_post(
+    State(timers): State<Arc<dyn TimerStore>>,
     Path(key): Path<String>,
-) -> Result<String> {
-    let lock = state.read().unwrap();
+) -> Result<impl IntoResponse, AppError> {
+    // the wall clock is only for the response; elapsed time comes from the
+    // store's monotonic clock
+    let saved_at = time::OffsetDateTime::now_utc().format(&Rfc3339)?;
+    timers.save(&key);
+    Ok(Json(json!({"key": key, "saved_at": saved_at})))
+}
 
-    if let Some(time) = lock.day12.get(&key) {
-        Ok(format!(
-            "{:?}",
-            time.elapsed().as_seconds_f64().floor() as i64
-        ))
+async fn day12_delete(
+    State(timers): State<Arc<dyn TimerStore>>,
+    Path(key): Path<String>,
+) -> StatusCode {
+    if timers.remove(&key) {
+        StatusCode::NO_CONTENT
     } else {
-        Err("key not found")?
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn day12_task1_get(
+    State(timers): State<Arc<dyn TimerStore>>,
+    Path(key): Path<String>,
+) -> Result<String, AppError> {
+    match timers.load(&key) {
+        Some(elapsed) => Ok(format!("{elapsed}")),
+        None => Err(AppError::not_found("key not found", json!({ "key": key }))),
     }
 }
 
-async fn day12_task2(Json(ulids): Json<Vec<String>>) -> Result<impl IntoResponse, AppError> {
+async fn day12_task2(AppJson(ulids): AppJson<Vec<String>>) -> Result<impl IntoResponse, AppError> {
     let ret = ulids
         .into_iter()
         .map(|s| {
-            let ulid = ulid::Ulid::from_string(&s)?;
+            let ulid = ulid::Ulid::from_string(&s).map_err(AppError::bad_request)?;
             Ok::<_, AppError>(uuid::Uuid::from_u128(ulid.0))
         })
         .rev()
@@ -287,11 +898,103 @@ async fn day12_task2(Json(ulids): Json<Vec<String>>) -> Result<impl IntoResponse
     Ok(Json(ret))
 }
 
+async fn day12_uuids(AppJson(uuids): AppJson<Vec<String>>) -> Result<impl IntoResponse, AppError> {
+    let ret = uuids
+        .into_iter()
+        .map(|s| {
+            let uuid = uuid::Uuid::parse_str(&s)
+                .map_err(|_| AppError::bad_request(anyhow::anyhow!("invalid uuid: {s}")))?;
+            Ok::<_, AppError>(ulid::Ulid(uuid.as_u128()).to_string())
+        })
+        .rev()
+        .collect::<Result<Vec<String>, AppError>>()?;
+    Ok(Json(ret))
+}
+
+struct UlidFacts {
+    ulid: ulid::Ulid,
+    datetime: time::OffsetDateTime,
+    lsb: bool,
+}
+
+impl UlidFacts {
+    fn is_christmas_eve(&self) -> bool {
+        self.datetime.month() as u8 == 12 && self.datetime.day() == 24
+    }
+
+    fn weekday(&self) -> u8 {
+        self.datetime.weekday().number_days_from_monday()
+    }
+}
+
+fn ulid_facts(s: &str) -> Result<UlidFacts, AppError> {
+    let invalid = || AppError::bad_request(anyhow::anyhow!("invalid ulid: {s}"));
+
+    let ulid = ulid::Ulid::from_string(s).map_err(|_| invalid())?;
+    let nanos = ulid.timestamp_ms() as i128 * 1_000_000;
+    let datetime = time::OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| invalid())?;
+
+    Ok(UlidFacts {
+        ulid,
+        datetime,
+        lsb: ulid.0 & 1 == 1,
+    })
+}
+
+async fn day12_ulid(Path(ulid): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let facts = ulid_facts(&ulid)?;
+
+    Ok(Json(json!({
+        "uuid": uuid::Uuid::from_u128(facts.ulid.0),
+        "timestamp_ms": facts.ulid.timestamp_ms(),
+        "datetime_rfc3339": facts.datetime.format(&Rfc3339)?,
+        "weekday": facts.weekday(),
+        "is_christmas_eve": facts.is_christmas_eve(),
+        "lsb": facts.lsb,
+    })))
+}
+
+#[derive(Deserialize)]
+struct Day12Timezone {
+    tz: Option<String>,
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+// either the `number_days_from_monday` index or the day's name
+fn parse_weekday(s: &str) -> Option<u8> {
+    match s.parse::<u8>() {
+        Ok(n) => (n < 7).then_some(n),
+        Err(_) => WEEKDAYS
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(s))
+            .map(|n| n as u8),
+    }
+}
+
 async fn day12_task3(
     Path(weekday): Path<String>,
-    Json(ulids): Json<Vec<String>>,
+    Query(query): Query<Day12Timezone>,
+    AppJson(ulids): AppJson<Vec<String>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let weekday: u8 = weekday.parse()?;
+    let weekday = parse_weekday(&weekday)
+        .ok_or_else(|| AppError::bad_request(anyhow::anyhow!("weekday must be 0..=6")))?;
+
+    let tz =
+        match &query.tz {
+            Some(name) => Some(time_tz::timezones::get_by_name(name).ok_or_else(|| {
+                AppError::bad_request(anyhow::anyhow!("unknown timezone: {name}"))
+            })?),
+            None => None,
+        };
 
     let mut christmas_eve = 0;
     let mut weekday_cnt = 0;
@@ -299,24 +1002,24 @@ async fn day12_task3(
     let mut lsb_is_1 = 0;
 
     for s in ulids {
-        let ulid = ulid::Ulid::from_string(&s)?;
-        let ts = ulid.datetime();
-        let epoch = ts.duration_since(std::time::SystemTime::UNIX_EPOCH)?;
-        let dt = time::OffsetDateTime::from_unix_timestamp_nanos(epoch.as_nanos() as i128)?;
+        let mut facts = ulid_facts(&s)?;
+        if let Some(tz) = tz {
+            facts.datetime = facts.datetime.to_timezone(tz);
+        }
 
-        if dt.month() as u8 == 12 && dt.day() == 24 {
+        if facts.is_christmas_eve() {
             christmas_eve += 1;
         }
 
-        if dt.weekday().number_days_from_monday() == weekday {
+        if facts.weekday() == weekday {
             weekday_cnt += 1;
         }
 
-        if dt > time::OffsetDateTime::now_utc() {
+        if facts.datetime > time::OffsetDateTime::now_utc() {
             in_the_future += 1;
         }
 
-        if ulid.0 & 1 == 1 {
+        if facts.lsb {
             lsb_is_1 += 1;
         }
     }
@@ -329,21 +1032,74 @@ async fn day12_task3(
     })))
 }
 
-async fn day13_task1(State(pool): State<Pool>) -> Result<String, AppError> {
-    let (res,) = sqlx::query_as::<_, (i32,)>("SELECT 20231213")
-        .fetch_one(&pool.pool)
-        .await?;
-    Ok(format!("{res}"))
-}
-
-async fn day13_18_reset(State(pool): State<Pool>) -> Result<(), AppError> {
-    let migrator = sqlx::migrate!();
-    migrator.undo(&pool.pool, 0).await?;
-    migrator.run(&pool.pool).await?;
-    Ok(())
+#[derive(Deserialize)]
+struct SqlQuery {
+    expr: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+// only plain integer arithmetic gets near the database: no identifiers, no
+// statement separators, no comments, and parentheses that stay balanced
+fn is_safe_arithmetic(expr: &str) -> bool {
+    let mut depth = 0_i32;
+    for c in expr.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '0'..='9' | '+' | '-' | '*' | '/' => {}
+            c if c.is_ascii_whitespace() => {}
+            _ => return false,
+        }
+        if depth < 0 {
+            return false;
+        }
+    }
+
+    depth == 0
+        && expr.chars().any(|c| c.is_ascii_digit())
+        && !expr.contains("--")
+        && !expr.contains("/*")
+}
+
+async fn day13_task1(
+    Query(query): Query<SqlQuery>,
+    State(pool): State<Pool>,
+) -> Result<String, AppError> {
+    let expr = query.expr.as_deref().unwrap_or("20231213");
+    if !is_safe_arithmetic(expr) {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "not an arithmetic expression: {expr}"
+        )))?
+    }
+
+    let (res,) = sqlx::query_as::<_, (i64,)>(&format!("SELECT ({expr})::BIGINT"))
+        .fetch_one(&pool.pool)
+        .await
+        .map_err(|err| match err {
+            // division by zero, overflow and the like are the caller's fault
+            sqlx::Error::Database(_) => AppError::bad_request(err),
+            err => err.into(),
+        })?;
+    Ok(format!("{res}"))
+}
+
+async fn day13_18_reset(State(pool): State<Pool>) -> Result<(), AppError> {
+    // concurrent resets would interleave the drop/create statements. the
+    // migrator can't run on a transaction from a handler (its futures aren't
+    // `Send` over a borrowed connection), so serialize them instead
+    let _guard = pool.reset_lock.lock().await;
+
+    let migrator = sqlx::migrate!();
+    migrator.undo(&pool.pool, 0).await?;
+    migrator.run(&pool.pool).await?;
+
+    sqlx::query("TRUNCATE orders, regions")
+        .execute(&pool.pool)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 struct Order {
     id: i32,
     region_id: i32,
@@ -357,12 +1113,14 @@ struct Region {
     name: String,
 }
 
-async fn day13_18_orders(
-    State(pool): State<Pool>,
-    Json(orders): Json<Vec<Order>>,
-) -> Result<(), AppError> {
+// orders whose id already exists are skipped, so retried batches are harmless;
+// returns how many rows were actually inserted
+async fn insert_orders(
+    executor: impl sqlx::PgExecutor<'_>,
+    orders: Vec<Order>,
+) -> Result<u64, sqlx::Error> {
     if orders.is_empty() {
-        return Ok(());
+        return Ok(0);
     }
 
     let mut query_builder =
@@ -374,17 +1132,16 @@ async fn day13_18_orders(
             .push_bind(order.gift_name)
             .push_bind(order.quantity);
     });
+    query_builder.push(" ON CONFLICT (id) DO NOTHING");
 
     let query = query_builder.build();
-    query.execute(&pool.pool).await?;
-
-    Ok(())
+    Ok(query.execute(executor).await?.rows_affected())
 }
 
-async fn day18_regions(
-    State(pool): State<Pool>,
-    Json(regions): Json<Vec<Region>>,
-) -> Result<(), AppError> {
+async fn insert_regions(
+    executor: impl sqlx::PgExecutor<'_>,
+    regions: Vec<Region>,
+) -> Result<(), sqlx::Error> {
     if regions.is_empty() {
         return Ok(());
     }
@@ -396,19 +1153,234 @@ async fn day18_regions(
     });
 
     let query = query_builder.build();
-    query.execute(&pool.pool).await?;
+    query.execute(executor).await?;
 
     Ok(())
 }
 
-async fn day13_task2_orders_total(State(pool): State<Pool>) -> Result<impl IntoResponse, AppError> {
-    let total = sqlx::query_as::<_, (i64,)>("SELECT SUM(quantity) FROM orders")
-        .fetch_one(&pool.pool)
+async fn day13_truncate_orders(State(pool): State<Pool>) -> Result<StatusCode, AppError> {
+    let _guard = pool.reset_lock.lock().await;
+    sqlx::query("TRUNCATE orders").execute(&pool.pool).await?;
+    pool.totals.send_replace(0);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn day18_truncate_regions(State(pool): State<Pool>) -> Result<StatusCode, AppError> {
+    let _guard = pool.reset_lock.lock().await;
+    // there's no foreign key to cascade along, so the orders that point at
+    // the regions are named explicitly
+    sqlx::query("TRUNCATE orders, regions")
+        .execute(&pool.pool)
+        .await?;
+    pool.totals.send_replace(0);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn day13_18_orders(
+    State(pool): State<Pool>,
+    AppJson(orders): AppJson<Vec<Order>>,
+) -> Result<impl IntoResponse, AppError> {
+    let inserted = insert_orders(&pool.pool, orders).await?;
+    if inserted > 0 {
+        pool.totals.send_replace(orders_total(&pool.pool).await?);
+    }
+    Ok(Json(json!({ "inserted": inserted })))
+}
+
+#[derive(Deserialize, Debug)]
+struct OrderFilter {
+    region_id: Option<i32>,
+    gift_name: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+async fn day13_list_orders(
+    Query(filter): Query<OrderFilter>,
+    State(pool): State<Pool>,
+) -> Result<Json<Vec<Order>>, AppError> {
+    let mut query_builder =
+        QueryBuilder::new("SELECT id, region_id, gift_name, quantity FROM orders WHERE TRUE");
+
+    if let Some(region_id) = filter.region_id {
+        query_builder.push(" AND region_id = ").push_bind(region_id);
+    }
+    if let Some(gift_name) = filter.gift_name {
+        query_builder.push(" AND gift_name = ").push_bind(gift_name);
+    }
+
+    query_builder.push(" ORDER BY id");
+    query_builder
+        .push(" LIMIT ")
+        .push_bind(filter.limit.map(i64::from));
+    query_builder
+        .push(" OFFSET ")
+        .push_bind(filter.offset.map(i64::from));
+
+    let orders = query_builder
+        .build_query_as::<Order>()
+        .fetch_all(&pool.pool)
+        .await?;
+
+    Ok(Json(orders))
+}
+
+async fn day18_regions(
+    State(pool): State<Pool>,
+    AppJson(regions): AppJson<Vec<Region>>,
+) -> Result<(), AppError> {
+    insert_regions(&pool.pool, regions).await?;
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct Seed {
+    #[serde(default)]
+    regions: Vec<Region>,
+    #[serde(default)]
+    orders: Vec<Order>,
+}
+
+async fn day18_seed(
+    State(pool): State<Pool>,
+    AppJson(seed): AppJson<Seed>,
+) -> Result<(), AppError> {
+    // regions and orders are committed together, so a failing order insert
+    // also discards the regions
+    let mut tx = pool.pool.begin().await?;
+    insert_regions(&mut *tx, seed.regions).await?;
+    let inserted = insert_orders(&mut *tx, seed.orders).await?;
+    tx.commit().await?;
+
+    if inserted > 0 {
+        pool.totals.send_replace(orders_total(&pool.pool).await?);
+    }
+    Ok(())
+}
+
+// SUM over no rows is NULL, which used to fail to decode and answer 500;
+// an empty table now totals 0
+async fn orders_total(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let total = sqlx::query_as::<_, (i64,)>("SELECT COALESCE(SUM(quantity), 0) FROM orders")
+        .fetch_one(pool)
         .await?;
-    Ok(Json(json!({ "total": total.0 })))
+    Ok(total.0)
+}
+
+async fn day13_task2_orders_total(State(pool): State<Pool>) -> Result<impl IntoResponse, AppError> {
+    let total = orders_total(&pool.pool).await?;
+    Ok(Json(json!({ "total": total })))
+}
+
+/// Waits for the next text frame, or `None` once the socket is closed.
+/// Pings and the closing handshake are answered by axum, and binary frames
+/// aren't part of any of our protocols, so those are skipped.
+async fn recv_ignoring_control(socket: &mut WebSocket) -> Option<String> {
+    loop {
+        match socket.recv().await? {
+            Ok(Message::Text(msg)) => return Some(msg),
+            Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_)) => continue,
+            Ok(Message::Close(_)) => {
+                // the reply is already queued; flush it so the closing
+                // handshake completes before the socket is dropped
+                let _ = socket.flush().await;
+                return None;
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+async fn day13_ws_totals(ws: WebSocketUpgrade, State(pool): State<Pool>) -> Response {
+    ws.on_upgrade(move |socket| day13_ws_totals_handle(pool, socket))
+}
+
+// sends the total on connect, on every tick, and whenever new orders come in
+async fn day13_ws_totals_handle(pool: Pool, mut socket: WebSocket) {
+    let mut totals = pool.totals.subscribe();
+    let mut ticks = tokio::time::interval(pool.totals_interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let total = tokio::select! {
+            _ = ticks.tick() => match orders_total(&pool.pool).await {
+                Ok(total) => total,
+                Err(_) => return,
+            },
+            changed = totals.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                *totals.borrow_and_update()
+            }
+            msg = recv_ignoring_control(&mut socket) => match msg {
+                // clients have nothing to say here
+                Some(_) => continue,
+                None => return,
+            },
+        };
+
+        let msg = json!({ "total": total }).to_string();
+        if socket.send(Message::Text(msg)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn day13_region_total(
+    State(pool): State<Pool>,
+    Path(region_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let total = sqlx::query_as::<_, (i64,)>(
+        "SELECT COALESCE(SUM(quantity), 0) FROM orders WHERE region_id = $1",
+    )
+    .bind(region_id)
+    .fetch_one(&pool.pool)
+    .await?;
+    Ok(Json(json!({ "region_id": region_id, "total": total.0 })))
+}
+
+enum Representation {
+    Json,
+    Csv,
+}
+
+impl Representation {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let accepts_csv = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|media| media.split(';').next().unwrap().trim() == "text/csv");
+
+        if accepts_csv {
+            Representation::Csv
+        } else {
+            Representation::Json
+        }
+    }
+}
+
+// RFC 4180: fields with separators, quotes or line breaks are quoted
+fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+#[derive(Deserialize)]
+struct TotalFilter {
+    min_total: Option<i64>,
 }
 
-async fn day18_total(State(pool): State<Pool>) -> Result<impl IntoResponse, AppError> {
+async fn day18_total(
+    headers: HeaderMap,
+    Query(filter): Query<TotalFilter>,
+    State(pool): State<Pool>,
+) -> Result<Response, AppError> {
     let row = sqlx::query_as::<_, (String, i64)>(
         "
         SELECT
@@ -417,12 +1389,22 @@ async fn day18_total(State(pool): State<Pool>) -> Result<impl IntoResponse, AppE
         FROM orders
         JOIN regions ON orders.region_id = regions.id
         GROUP BY orders.region_id, regions.id
+        HAVING $1::BIGINT IS NULL OR SUM(orders.quantity) >= $1
         ORDER BY regions.name
     ",
     )
+    .bind(filter.min_total)
     .fetch_all(&pool.pool)
     .await?;
 
+    if let Representation::Csv = Representation::from_headers(&headers) {
+        let mut csv = "region,total\n".to_string();
+        for (region, total) in row {
+            csv.push_str(&format!("{},{total}\n", csv_field(&region)));
+        }
+        return Ok(([(header::CONTENT_TYPE, "text/csv")], csv).into_response());
+    }
+
     let res = row
         .into_iter()
         .map(|(region, total)| {
@@ -433,7 +1415,7 @@ async fn day18_total(State(pool): State<Pool>) -> Result<impl IntoResponse, AppE
         })
         .collect::<Vec<_>>();
 
-    Ok(Json(res))
+    Ok(Json(res).into_response())
 }
 
 async fn day13_task2_orders_popular(
@@ -458,18 +1440,56 @@ async fn day13_task2_orders_popular(
     Ok(Json(json!({"popular": res})))
 }
 
+// a single top gift is returned as is, ties as an array of all of them
+async fn day13_orders_popular_by_quantity(
+    State(pool): State<Pool>,
+) -> Result<impl IntoResponse, AppError> {
+    let rows = sqlx::query_as::<_, (String,)>(
+        "
+        WITH totals AS (
+            SELECT gift_name, SUM(quantity) AS total
+            FROM orders
+            GROUP BY gift_name
+        )
+        SELECT gift_name
+        FROM totals
+        WHERE total = (SELECT MAX(total) FROM totals)
+        ORDER BY gift_name
+    ",
+    )
+    .fetch_all(&pool.pool)
+    .await?;
+
+    let res = match rows.as_slice() {
+        [] => json!(null),
+        [(gift,)] => json!(gift),
+        tied => json!(tied.iter().map(|(gift,)| gift).collect::<Vec<_>>()),
+    };
+
+    Ok(Json(json!({"popular": res})))
+}
+
+#[derive(Deserialize)]
+struct RegionWindow {
+    region_offset: Option<u32>,
+    region_limit: Option<u32>,
+}
+
+#[tracing::instrument(skip_all, fields(limit = limit))]
 async fn day18_top_list(
     Path(limit): Path<i32>,
+    Query(window): Query<RegionWindow>,
     State(pool): State<Pool>,
 ) -> Result<impl IntoResponse, AppError> {
     let row = sqlx::query_as::<_, (String, Vec<String>)>(
         "
         SELECT
             sum.region_name AS region,
-            ARRAY_REMOVE(
+            COALESCE(
                 (ARRAY_AGG(
                     sum.gift_name ORDER BY sum.quantity DESC, sum.gift_name ASC
-                ))[:$1], NULL
+                ) FILTER (WHERE sum.gift_name IS NOT NULL))[:$1],
+                '{}'
             ) AS top_gifts
         FROM (
             SELECT
@@ -482,9 +1502,12 @@ async fn day18_top_list(
         ) AS sum
         GROUP BY sum.region_name
         ORDER BY sum.region_name ASC
+        LIMIT $2 OFFSET $3
     ",
     )
     .bind(limit)
+    .bind(window.region_limit.map(i64::from))
+    .bind(window.region_offset.map(i64::from))
     .fetch_all(&pool.pool)
     .await?;
 
@@ -500,37 +1523,48 @@ async fn day18_top_list(
     Ok(Json(ret))
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 struct Day14 {
     content: String,
+    title: Option<String>,
 }
 
-async fn day14_task1(Json(input): Json<Day14>) -> String {
-    format!(
-        r"<html>
-  <head>
-    <title>CCH23 Day 14</title>
-  </head>
-  <body>
-    {}
-  </body>
-</html>",
-        input.content
-    )
+#[derive(Template)]
+#[template(path = "day14.html")]
+struct Day14Page<'a> {
+    title: &'a str,
+    content: &'a str,
 }
 
-async fn day14_task2(Json(input): Json<Day14>) -> String {
-    format!(
-        r"<html>
-  <head>
-    <title>CCH23 Day 14</title>
-  </head>
-  <body>
-    {}
-  </body>
-</html>",
-        html_escape::encode_double_quoted_attribute(&input.content)
-    )
+async fn day14_task1(AppJson(input): AppJson<Day14>) -> Result<Html<String>, AppError> {
+    let page = Day14Page {
+        title: input.title.as_deref().unwrap_or("CCH23 Day 14"),
+        content: &input.content,
+    };
+    Ok(Html(page.render()?))
+}
+
+async fn day14_task2(AppJson(input): AppJson<Day14>) -> Result<Html<String>, AppError> {
+    let content = html_escape::encode_double_quoted_attribute(&input.content);
+    let page = Day14Page {
+        title: input.title.as_deref().unwrap_or("CCH23 Day 14"),
+        content: &content,
+    };
+    Ok(Html(page.render()?))
+}
+
+// HEAD renders the same page as POST, so Content-Length matches; a HEAD
+// without a body gets the headers of the page for empty content
+fn day14_head_input(input: Option<AppJson<Day14>>) -> AppJson<Day14> {
+    input.unwrap_or(AppJson(Day14::default()))
+}
+
+async fn day14_task1_head(input: Option<AppJson<Day14>>) -> Result<Html<String>, AppError> {
+    day14_task1(day14_head_input(input)).await
+}
+
+async fn day14_task2_head(input: Option<AppJson<Day14>>) -> Result<Html<String>, AppError> {
+    day14_task2(day14_head_input(input)).await
 }
 
 #[derive(Deserialize, Debug)]
@@ -538,8 +1572,11 @@ struct Day15 {
     input: String,
 }
 
-async fn day15_task1(Json(input): Json<Day15>) -> impl IntoResponse {
-    let (code, resp) = if is_nice(&input.input) {
+async fn day15_task1(
+    State(rules): State<Arc<NiceRules>>,
+    AppJson(input): AppJson<Day15>,
+) -> impl IntoResponse {
+    let (code, resp) = if rules.evaluate(&input.input) {
         (StatusCode::OK, "nice")
     } else {
         (StatusCode::BAD_REQUEST, "naughty")
@@ -548,82 +1585,187 @@ async fn day15_task1(Json(input): Json<Day15>) -> impl IntoResponse {
     (code, Json(json!({ "result": resp })))
 }
 
+struct NiceRules {
+    min_vowels: usize,
+    require_double: bool,
+    vowels: String,
+    forbidden: Vec<String>,
+}
+
+impl Default for NiceRules {
+    fn default() -> Self {
+        Self {
+            min_vowels: 3,
+            require_double: true,
+            vowels: "aeiouy".to_string(),
+            forbidden: ["ab", "cd", "pq", "xy"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl NiceRules {
+    // the stock rules, with the vowels and forbidden pairs from the config
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            vowels: config.nice_vowels.clone(),
+            forbidden: config.nice_forbidden.clone(),
+            ..Self::default()
+        }
+    }
+
+    fn evaluate(&self, s: &str) -> bool {
+        let vowels = s.chars().filter(|c| self.vowels.contains(*c)).count();
+        let twice = s
+            .as_bytes()
+            .windows(2)
+            .any(|w| w[0] == w[1] && w[0].is_ascii_alphabetic());
+        let err = self.forbidden.iter().any(|p| s.contains(p.as_str()));
+
+        vowels >= self.min_vowels && (twice || !self.require_double) && !err
+    }
+}
+
+// `/15/nice` goes through the configured rules; this is the stock ruleset
+#[allow(dead_code)]
 fn is_nice(s: &str) -> bool {
-    let vowels = s.chars().filter(|c| "aeiouy".contains(*c)).count();
-    let twice = s
-        .as_bytes()
-        .windows(2)
-        .any(|w| w[0] == w[1] && w[0].is_ascii_alphabetic());
-    let err = ["ab", "cd", "pq", "xy"].iter().any(|p| s.contains(p));
-
-    vowels >= 3 && twice && !err
-}
-
-async fn day15_task2(Json(input): Json<Day15>) -> impl IntoResponse {
-    let s = &input.input;
-
-    let len = s.len();
-    let uppercase = s.chars().any(|c| c.is_uppercase());
-    let lowercase = s.chars().any(|c| c.is_lowercase());
-    let digit = s.chars().filter(|c| c.is_ascii_digit()).count();
-    let sum = s
-        .chars()
-        .map(|c| if c.is_ascii_digit() { c } else { ' ' })
-        .collect::<String>()
-        .split_ascii_whitespace()
-        .map(|w| w.parse::<i64>().unwrap())
-        .sum::<i64>();
-
-    let re_joy = regex::Regex::new(r"j.*o.*y").unwrap();
-    let re_no_joy = &[
-        regex::Regex::new(r"j.*y.*o").unwrap(),
-        regex::Regex::new(r"o.*j.*y").unwrap(),
-        regex::Regex::new(r"o.*y.*j").unwrap(),
-        regex::Regex::new(r"y.*j.*o").unwrap(),
-        regex::Regex::new(r"y.*o.*j").unwrap(),
-    ];
-    let joy = re_joy.is_match(s) && !re_no_joy.iter().any(|re| re.is_match(s));
-    let rep = s.as_bytes().windows(3).any(|w| {
-        w[0] == w[2] && w[0] != w[1] && w[0].is_ascii_alphabetic() && w[1].is_ascii_alphabetic()
-    });
-    let unicode = s.chars().any(|c| ('\u{2980}'..='\u{2BFF}').contains(&c));
-    let emoji = s.chars().any(unic::emoji::char::is_emoji_presentation);
-    let digest = sha256::digest(s.as_bytes());
-
-    let (code, resp) = match () {
-        _ if len < 8 => (400, "8 chars"),
-        _ if !uppercase || !lowercase || digit == 0 => (400, "more types of chars"),
-        _ if digit < 5 => (400, "55555"),
-        _ if sum != 2023 => (400, "math is hard"),
-        _ if !joy => (406, "not joyful enough"),
-        _ if !rep => (451, "illegal: no sandwich"),
-        _ if !unicode => (416, "outranged"),
-        _ if !emoji => (426, "😳"),
-        _ if !digest.ends_with('a') => (418, "not a coffee brewer"),
-        _ => (200, "that's a nice password"),
+    NiceRules::default().evaluate(s)
+}
+
+#[derive(Deserialize)]
+struct Day15Verbose {
+    #[serde(default)]
+    verbose: bool,
+}
+
+/// The outcome of every `/15/game` rule, in rule order.
+#[derive(Serialize)]
+struct RuleChecks {
+    length: bool,
+    char_types: bool,
+    digit_count: bool,
+    sum: bool,
+    joy: bool,
+    sandwich: bool,
+    unicode_range: bool,
+    emoji: bool,
+    sha_suffix: bool,
+}
+
+impl RuleChecks {
+    fn new(s: &str) -> Self {
+        let uppercase = s.chars().any(|c| c.is_uppercase());
+        let lowercase = s.chars().any(|c| c.is_lowercase());
+        let digit = s.chars().filter(|c| c.is_ascii_digit()).count();
+        let sum = s
+            .chars()
+            .map(|c| if c.is_ascii_digit() { c } else { ' ' })
+            .collect::<String>()
+            .split_ascii_whitespace()
+            .try_fold(0_i128, |acc, w| acc.checked_add(w.parse().ok()?));
+
+        let re_joy = regex::Regex::new(r"j.*o.*y").unwrap();
+        let re_no_joy = &[
+            regex::Regex::new(r"j.*y.*o").unwrap(),
+            regex::Regex::new(r"o.*j.*y").unwrap(),
+            regex::Regex::new(r"o.*y.*j").unwrap(),
+            regex::Regex::new(r"y.*j.*o").unwrap(),
+            regex::Regex::new(r"y.*o.*j").unwrap(),
+        ];
+        let joy = re_joy.is_match(s) && !re_no_joy.iter().any(|re| re.is_match(s));
+        let rep = s.as_bytes().windows(3).any(|w| {
+            w[0] == w[2] && w[0] != w[1] && w[0].is_ascii_alphabetic() && w[1].is_ascii_alphabetic()
+        });
+        let unicode = s.chars().any(|c| ('\u{2980}'..='\u{2BFF}').contains(&c));
+        let emoji = s.chars().any(unic::emoji::char::is_emoji_presentation);
+        let digest = sha256::digest(s.as_bytes());
+
+        Self {
+            length: s.len() >= 8,
+            char_types: uppercase && lowercase && digit > 0,
+            digit_count: digit >= 5,
+            sum: sum == Some(2023),
+            joy,
+            sandwich: rep,
+            unicode_range: unicode,
+            emoji,
+            sha_suffix: digest.ends_with('a'),
+        }
+    }
+
+    fn nice(&self) -> bool {
+        self.length
+            && self.char_types
+            && self.digit_count
+            && self.sum
+            && self.joy
+            && self.sandwich
+            && self.unicode_range
+            && self.emoji
+            && self.sha_suffix
+    }
+}
+
+async fn day15_task2(
+    Query(query): Query<Day15Verbose>,
+    AppJson(input): AppJson<Day15>,
+) -> impl IntoResponse {
+    let checks = RuleChecks::new(&input.input);
+
+    let (rule, code, resp) = match () {
+        _ if !checks.length => (Some(1), 400, "8 chars"),
+        _ if !checks.char_types => (Some(2), 400, "more types of chars"),
+        _ if !checks.digit_count => (Some(3), 400, "55555"),
+        _ if !checks.sum => (Some(4), 400, "math is hard"),
+        _ if !checks.joy => (Some(5), 406, "not joyful enough"),
+        _ if !checks.sandwich => (Some(6), 451, "illegal: no sandwich"),
+        _ if !checks.unicode_range => (Some(7), 416, "outranged"),
+        _ if !checks.emoji => (Some(8), 426, "😳"),
+        _ if !checks.sha_suffix => (Some(9), 418, "not a coffee brewer"),
+        _ => (None, 200, "that's a nice password"),
     };
 
-    (
-        StatusCode::from_u16(code).unwrap(),
-        Json(json!({
-            "result": if code == 200 { "nice" } else { "naughty" },
-            "reason": resp,
-        })),
-    )
+    let mut body = json!({
+        "result": if code == 200 { "nice" } else { "naughty" },
+        "reason": resp,
+    });
+
+    if query.verbose {
+        body["rule"] = json!(rule);
+    }
+
+    (StatusCode::from_u16(code).unwrap(), Json(body))
+}
+
+async fn day15_analyze(AppJson(input): AppJson<Day15>) -> impl IntoResponse {
+    let checks = RuleChecks::new(&input.input);
+    let result = if checks.nice() { "nice" } else { "naughty" };
+    Json(json!({ "result": result, "rules": checks }))
 }
 
-async fn day19_task1(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(day19_task1_handle)
+fn shutdown_frame() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: close_code::AWAY,
+        reason: "server is shutting down".into(),
+    }))
+}
+
+async fn day19_task1(State(state): State<TwitterState>, ws: WebSocketUpgrade) -> Response {
+    if state.shutdown.is_cancelled() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    ws.on_upgrade(move |socket| day19_task1_handle(socket, state.shutdown))
 }
 
-async fn day19_task1_handle(mut socket: WebSocket) {
+async fn day19_task1_handle(mut socket: WebSocket, shutdown: CancellationToken) {
     let mut started = false;
 
-    while let Some(msg) = socket.recv().await {
-        let Ok(msg) = msg else {
-            return;
+    loop {
+        let msg = tokio::select! {
+            msg = recv_ignoring_control(&mut socket) => msg,
+            _ = shutdown.cancelled() => break,
         };
-        let Ok(msg) = msg.to_text() else {
+        let Some(msg) = msg else {
             return;
         };
 
@@ -636,6 +1778,23 @@ async fn day19_task1_handle(mut socket: WebSocket) {
             return;
         }
     }
+
+    let _ = socket.send(shutdown_frame()).await;
+}
+
+async fn day19_echo(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(day19_echo_handle)
+}
+
+async fn day19_echo_handle(mut socket: WebSocket) {
+    let mut seq = 0;
+
+    while let Some(msg) = recv_ignoring_control(&mut socket).await {
+        seq += 1;
+        if socket.send(format!("{seq}: {msg}").into()).await.is_err() {
+            return;
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -649,37 +1808,157 @@ struct TweetMessage {
     message: String,
 }
 
-#[derive(Clone, Default)]
+const TWEET_RATE_LIMIT: u32 = 10;
+const TWEET_RATE_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
 struct TwitterState {
     views: Arc<AtomicUsize>,
+    room_views: Arc<RwLock<HashMap<usize, AtomicUsize>>>,
     rooms: Arc<Mutex<HashMap<usize, Room>>>,
+    // rooms listed here require a matching `?token=` to join
+    room_tokens: Arc<HashMap<usize, String>>,
+    // in grapheme clusters, so an emoji sequence like a family counts as one
+    max_tweet_len: usize,
+    // tweets buffered per room; subscribers further behind skip ahead
+    room_capacity: usize,
+    ping_interval: Duration,
+    shutdown: CancellationToken,
+}
+
+impl TwitterState {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            views: Default::default(),
+            room_views: Default::default(),
+            rooms: Default::default(),
+            room_tokens: Arc::new(config.room_tokens.clone()),
+            max_tweet_len: config.tweet_max_len,
+            room_capacity: config.room_capacity.max(1),
+            ping_interval: config.ws_ping_interval,
+            shutdown: Default::default(),
+        }
+    }
+}
+
+const ROOM_HISTORY_LEN: usize = 50;
+
+fn truncate_graphemes(s: &str, max_len: usize) -> String {
+    s.graphemes(true).take(max_len).collect()
 }
 
 struct Room {
     tx: Sender<Tweet>,
+    history: VecDeque<Tweet>,
+}
+
+impl Room {
+    fn new(capacity: usize) -> Self {
+        let (tx, _rx) = tokio::sync::broadcast::channel(capacity);
+        Room {
+            tx,
+            history: VecDeque::with_capacity(ROOM_HISTORY_LEN),
+        }
+    }
 }
 
 impl TwitterState {
-    fn join(&self, room: usize) -> (Sender<Tweet>, Receiver<Tweet>) {
+    fn join(&self, room: usize) -> (Vec<Tweet>, Receiver<Tweet>) {
+        // history and subscription are taken under the same lock as `post`,
+        // so no tweet is missed or duplicated in between
         let mut room_lock = self.rooms.lock().unwrap();
-        let room = room_lock.entry(room).or_insert_with(|| {
-            let (tx, _rx) = tokio::sync::broadcast::channel(1_000_000);
-            Room { tx }
-        });
-        (room.tx.clone(), room.tx.subscribe())
+        let room = room_lock
+            .entry(room)
+            .or_insert_with(|| Room::new(self.room_capacity));
+        (room.history.iter().cloned().collect(), room.tx.subscribe())
+    }
+
+    fn post(&self, room: usize, tweet: Tweet) {
+        let mut room_lock = self.rooms.lock().unwrap();
+        let room = room_lock
+            .entry(room)
+            .or_insert_with(|| Room::new(self.room_capacity));
+        if room.history.len() == ROOM_HISTORY_LEN {
+            room.history.pop_front();
+        }
+        room.history.push_back(tweet.clone());
+        // sending only fails when nobody is listening, which is fine
+        let _ = room.tx.send(tweet);
+    }
+
+    /// Applies the tweet rules, truncating first when asked to. The error is
+    /// the frame (or body) to answer with.
+    fn check_tweet(&self, message: &mut String, truncate: bool) -> Result<(), serde_json::Value> {
+        // newlines and escapes mess up the clients' timelines
+        if message.chars().any(char::is_control) {
+            return Err(json!({"error": "control_characters"}));
+        }
+        if truncate {
+            *message = truncate_graphemes(message, self.max_tweet_len);
+        }
+        if message.graphemes(true).count() > self.max_tweet_len {
+            return Err(json!({"error": "message_too_long", "max": self.max_tweet_len}));
+        }
+        Ok(())
+    }
+
+    fn authorize(&self, room: usize, token: Option<&str>) -> bool {
+        match self.room_tokens.get(&room) {
+            Some(secret) => token == Some(secret.as_str()),
+            None => true,
+        }
+    }
+
+    fn rooms(&self) -> Vec<RoomStats> {
+        // `join` never removes rooms, so drop the ones nobody listens to
+        // anymore. rooms with history stay around for whoever joins next
+        let mut room_lock = self.rooms.lock().unwrap();
+        room_lock.retain(|_, room| room.tx.receiver_count() > 0 || !room.history.is_empty());
+
+        let mut ret = room_lock
+            .iter()
+            .filter(|(_, r)| r.tx.receiver_count() > 0)
+            .map(|(&room, r)| RoomStats {
+                room,
+                subscribers: r.tx.receiver_count(),
+            })
+            .collect::<Vec<_>>();
+        ret.sort_by_key(|stats| stats.room);
+        ret
     }
 
-    fn inc_views(&self) {
+    fn inc_views(&self, room: usize) {
         self.views.fetch_add(1, Ordering::SeqCst);
+
+        // the write lock is only needed the first time a room is seen
+        if let Some(views) = self.room_views.read().unwrap().get(&room) {
+            views.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        self.room_views
+            .write()
+            .unwrap()
+            .entry(room)
+            .or_default()
+            .fetch_add(1, Ordering::SeqCst);
     }
 
     fn reset_views(&self) {
         self.views.store(0, Ordering::SeqCst);
+        self.room_views.write().unwrap().clear();
     }
 
     fn views(&self) -> usize {
         self.views.load(Ordering::SeqCst)
     }
+
+    fn room_views(&self, room: usize) -> usize {
+        self.room_views
+            .read()
+            .unwrap()
+            .get(&room)
+            .map_or(0, |views| views.load(Ordering::SeqCst))
+    }
 }
 
 async fn day19_task2_reset(State(state): State<TwitterState>) {
@@ -691,95 +1970,539 @@ async fn day19_task2_views(State(state): State<TwitterState>) -> String {
     format!("{views}")
 }
 
-async fn day19_task2(
-    Path((room, user)): Path<(usize, String)>,
-    State(state): State<TwitterState>,
-    ws: WebSocketUpgrade,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| day19_task2_handle(room, user, state, socket))
+async fn day19_room_views(State(state): State<TwitterState>, Path(room): Path<usize>) -> String {
+    let views = state.room_views(room);
+    format!("{views}")
 }
 
-async fn day19_task2_handle(room: usize, user: String, state: TwitterState, socket: WebSocket) {
-    let (tx, rx) = state.join(room);
+#[derive(Serialize)]
+struct RoomStats {
+    room: usize,
+    subscribers: usize,
+}
 
-    let rx = BroadcastStream::new(rx).map(Either::Right);
+async fn day19_rooms(State(state): State<TwitterState>) -> Json<Vec<RoomStats>> {
+    Json(state.rooms())
+}
 
-    let (mut socket_sink, socket_stream) = socket.split();
+#[derive(Deserialize)]
+struct RoomQuery {
+    token: Option<String>,
+    // cut over-long tweets short instead of rejecting them
+    #[serde(default)]
+    truncate: bool,
+}
+
+#[derive(Serialize)]
+struct TwitterStats {
+    views: usize,
+    rooms: usize,
+    total_subscribers: usize,
+}
+
+async fn day19_stats(State(state): State<TwitterState>) -> Json<TwitterStats> {
+    let rooms = state.rooms();
+    Json(TwitterStats {
+        views: state.views(),
+        rooms: rooms.len(),
+        total_subscribers: rooms.iter().map(|stats| stats.subscribers).sum(),
+    })
+}
+
+async fn day19_task2(
+    Path((room, user)): Path<(usize, String)>,
+    Query(query): Query<RoomQuery>,
+    State(state): State<TwitterState>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if state.shutdown.is_cancelled() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    if !state.authorize(room, query.token.as_deref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    ws.on_upgrade(move |socket| day19_task2_handle(room, user, query.truncate, state, socket))
+}
+
+// same rules as tweets sent over the room's websocket, minus the per-socket
+// rate limit; subscribers count views as usual when it reaches them
+async fn day19_post_tweet(
+    Path((room, user)): Path<(usize, String)>,
+    Query(query): Query<RoomQuery>,
+    State(state): State<TwitterState>,
+    AppJson(mut msg): AppJson<TweetMessage>,
+) -> Response {
+    if !state.authorize(room, query.token.as_deref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    if let Err(body) = state.check_tweet(&mut msg.message, query.truncate) {
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+
+    state.post(
+        room,
+        Tweet {
+            user,
+            message: msg.message,
+        },
+    );
+    StatusCode::ACCEPTED.into_response()
+}
+
+async fn day19_task2_handle(
+    room: usize,
+    user: String,
+    truncate: bool,
+    state: TwitterState,
+    socket: WebSocket,
+) {
+    let (history, rx) = state.join(room);
+
+    let rx = BroadcastStream::new(rx).map(Either::Right);
+
+    let (mut socket_sink, socket_stream) = socket.split();
+
+    // replayed history is not counted as views
+    for tweet in history {
+        if socket_sink
+            .send(Message::Text(serde_json::to_string(&tweet).unwrap()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
 
     let socket = socket_stream.map(Either::Left);
     let mut r = stream_select!(rx, socket);
+    let limit = f64::from(TWEET_RATE_LIMIT);
+    let mut bucket = TokenBucket::new(limit, limit / TWEET_RATE_PERIOD.as_secs_f64());
+
+    // a client that hasn't answered the previous ping by the next one is gone
+    let mut keepalive = tokio::time::interval_at(
+        tokio::time::Instant::now() + state.ping_interval,
+        state.ping_interval,
+    );
+    keepalive.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut awaiting_pong = false;
+
+    loop {
+        let msg = tokio::select! {
+            msg = r.next() => msg,
+            _ = keepalive.tick() => {
+                if awaiting_pong || socket_sink.send(Message::Ping(vec![])).await.is_err() {
+                    return;
+                }
+                awaiting_pong = true;
+                continue;
+            }
+            _ = state.shutdown.cancelled() => break,
+        };
+        let Some(msg) = msg else {
+            return;
+        };
 
-    while let Some(msg) = r.next().await {
         match msg {
             Either::Left(msg) => {
-                let Ok(msg) = msg else {
-                    return;
+                let msg = match msg {
+                    Ok(Message::Pong(_)) => {
+                        awaiting_pong = false;
+                        continue;
+                    }
+                    // pings are answered by axum
+                    Ok(Message::Ping(_)) => continue,
+                    Ok(msg) => msg,
+                    Err(_) => return,
                 };
                 let Ok(msg) = msg.to_text() else {
                     return;
                 };
-                let Ok(msg) = serde_json::from_str::<TweetMessage>(msg) else {
+                let Ok(mut msg) = serde_json::from_str::<TweetMessage>(msg) else {
                     return;
                 };
-                if msg.message.len() > 128 {
-                    continue;
-                }
-                tx.send(Tweet {
-                    user: user.clone(),
-                    message: msg.message,
-                })
-                .unwrap();
-            }
-            Either::Right(tweet) => {
-                if let Ok(tweet) = tweet {
-                    state.inc_views();
+                if let Err(frame) = state.check_tweet(&mut msg.message, truncate) {
                     if socket_sink
-                        .send(Message::Text(serde_json::to_string(&tweet).unwrap()))
+                        .send(Message::Text(frame.to_string()))
                         .await
                         .is_err()
                     {
                         return;
                     }
+                    continue;
+                }
+                if bucket.try_take().is_err() {
+                    let frame = json!({"error": "rate_limited"}).to_string();
+                    if socket_sink.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                state.post(
+                    room,
+                    Tweet {
+                        user: user.clone(),
+                        message: msg.message,
+                    },
+                );
+            }
+            Either::Right(tweet) => {
+                let tweet = match tweet {
+                    Ok(tweet) => tweet,
+                    // the stream resumes after lagging; the skipped tweets are
+                    // lost, but the client is told how many
+                    Err(BroadcastStreamRecvError::Lagged(count)) => {
+                        let frame = json!({"warning": "dropped", "count": count}).to_string();
+                        if socket_sink.send(Message::Text(frame)).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                state.inc_views(room);
+                if socket_sink
+                    .send(Message::Text(serde_json::to_string(&tweet).unwrap()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    let _ = socket_sink.send(shutdown_frame()).await;
+}
+
+fn archive_reader(body: Bytes) -> Box<dyn Read> {
+    if body.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::GzDecoder::new(body.reader()))
+    } else {
+        Box::new(body.reader())
+    }
+}
+
+enum Archive {
+    Tar(tar::Archive<Box<dyn Read>>),
+    Zip(zip::ZipArchive<Cursor<Bytes>>),
+}
+
+// zip needs random access, so it's told apart up front rather than wrapped
+// into a reader like gzip
+fn open_archive(body: Bytes) -> Result<Archive, AppError> {
+    if body.starts_with(b"PK\x03\x04") {
+        let zip = zip::ZipArchive::new(Cursor::new(body)).map_err(AppError::bad_request)?;
+        Ok(Archive::Zip(zip))
+    } else {
+        Ok(Archive::Tar(tar::Archive::new(archive_reader(body))))
+    }
+}
+
+#[derive(Serialize)]
+struct ArchiveEntry {
+    name: String,
+    size: u64,
+    entry_type: &'static str,
+}
+
+fn entry_type_name(entry_type: tar::EntryType) -> &'static str {
+    match entry_type {
+        tar::EntryType::Regular => "regular",
+        tar::EntryType::Link => "hardlink",
+        tar::EntryType::Symlink => "symlink",
+        tar::EntryType::Char => "char",
+        tar::EntryType::Block => "block",
+        tar::EntryType::Directory => "directory",
+        tar::EntryType::Fifo => "fifo",
+        tar::EntryType::Continuous => "continuous",
+        tar::EntryType::GNULongName => "gnu_longname",
+        tar::EntryType::GNULongLink => "gnu_longlink",
+        tar::EntryType::GNUSparse => "gnu_sparse",
+        tar::EntryType::XGlobalHeader => "pax_global_header",
+        tar::EntryType::XHeader => "pax_header",
+        _ => "other",
+    }
+}
+
+// some tools write regular files as contiguous or sparse entries; pax and
+// gnu longname headers only describe the entry after them
+fn is_file_type(entry_type: &str) -> bool {
+    matches!(entry_type, "regular" | "continuous" | "gnu_sparse")
+}
+
+impl ArchiveEntry {
+    fn is_file(&self) -> bool {
+        is_file_type(self.entry_type)
+    }
+}
+
+fn is_zip_symlink(file: &zip::read::ZipFile) -> bool {
+    file.unix_mode()
+        .is_some_and(|mode| mode & 0o170000 == 0o120000)
+}
+
+impl Archive {
+    fn entries(&mut self) -> Result<Vec<ArchiveEntry>, AppError> {
+        let mut ret = vec![];
+
+        match self {
+            Archive::Tar(archive) => {
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    let entry_type = entry.header().entry_type();
+                    let size = if entry_type.is_dir() {
+                        0
+                    } else {
+                        entry.header().size()?
+                    };
+
+                    ret.push(ArchiveEntry {
+                        name: entry.path()?.to_string_lossy().into_owned(),
+                        size,
+                        entry_type: entry_type_name(entry_type),
+                    });
+                }
+            }
+            Archive::Zip(archive) => {
+                for i in 0..archive.len() {
+                    let file = archive.by_index(i).map_err(AppError::bad_request)?;
+                    let entry_type = if file.is_dir() {
+                        "directory"
+                    } else if is_zip_symlink(&file) {
+                        "symlink"
+                    } else {
+                        "regular"
+                    };
+
+                    ret.push(ArchiveEntry {
+                        name: file.name().to_string(),
+                        size: if file.is_dir() { 0 } else { file.size() },
+                        entry_type,
+                    });
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// sha256 of every file's contents, keyed by its name.
+    fn file_digests(&mut self) -> Result<BTreeMap<String, String>, AppError> {
+        let mut ret = BTreeMap::new();
+
+        match self {
+            Archive::Tar(archive) => {
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    if !is_file_type(entry_type_name(entry.header().entry_type())) {
+                        continue;
+                    }
+
+                    let name = entry.path()?.to_string_lossy().into_owned();
+                    let mut contents = vec![];
+                    entry.read_to_end(&mut contents)?;
+                    ret.insert(name, sha256::digest(contents.as_slice()));
+                }
+            }
+            Archive::Zip(archive) => {
+                for i in 0..archive.len() {
+                    let mut file = archive.by_index(i).map_err(AppError::bad_request)?;
+                    if file.is_dir() || is_zip_symlink(&file) {
+                        continue;
+                    }
+
+                    let name = file.name().to_string();
+                    let mut contents = vec![];
+                    file.read_to_end(&mut contents)?;
+                    ret.insert(name, sha256::digest(contents.as_slice()));
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn unpack(self, dst: &std::path::Path) -> Result<(), AppError> {
+        let escapes = |path: &std::path::Path| {
+            AppError::bad_request(anyhow::anyhow!(
+                "archive entry escapes the target directory: {}",
+                path.display()
+            ))
+        };
+
+        match self {
+            Archive::Tar(mut archive) => {
+                archive.set_preserve_permissions(false);
+
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+
+                    let entry_type = entry.header().entry_type();
+                    if entry_type.is_symlink() || entry_type.is_hard_link() {
+                        continue;
+                    }
+
+                    let path = entry.path()?.into_owned();
+                    if path
+                        .components()
+                        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+                    {
+                        Err(escapes(&path))?
+                    }
+
+                    entry.unpack_in(dst)?;
+                }
+            }
+            Archive::Zip(mut archive) => {
+                for i in 0..archive.len() {
+                    let mut file = archive.by_index(i).map_err(AppError::bad_request)?;
+                    if is_zip_symlink(&file) {
+                        continue;
+                    }
+
+                    let path = file
+                        .enclosed_name()
+                        .ok_or_else(|| escapes(std::path::Path::new(file.name())))?
+                        .to_owned();
+                    let path = dst.join(path);
+
+                    if file.is_dir() {
+                        fs::create_dir_all(&path)?;
+                    } else {
+                        if let Some(parent) = path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        std::io::copy(&mut file, &mut fs::File::create(&path)?)?;
+                    }
                 }
             }
         }
+
+        Ok(())
     }
 }
 
 async fn day20_archive_files(body: Bytes) -> Result<String, AppError> {
-    let mut archive = tar::Archive::new(body.reader());
-    let file_num = archive
+    let file_num = open_archive(body)?
         .entries()?
-        .filter(|e| matches!(e, Ok(e) if e.header().entry_type() == tar::EntryType::Regular))
+        .iter()
+        .filter(|e| e.is_file())
         .count();
     Ok(format!("{file_num}"))
 }
 
 async fn day20_archive_files_size(body: Bytes) -> Result<String, AppError> {
-    let mut archive = tar::Archive::new(body.reader());
-    let total_size = archive
+    let total_size = open_archive(body)?
         .entries()?
-        .filter_map(|e| {
-            if let Ok(e) = e {
-                if e.header().entry_type() == tar::EntryType::Regular {
-                    return Some(e.header().size().unwrap());
-                }
-            }
-            None
-        })
+        .iter()
+        .filter(|e| e.is_file())
+        .map(|e| e.size)
         .sum::<u64>();
     Ok(format!("{total_size}"))
 }
 
-async fn day20_cookie(body: Bytes) -> Result<String, AppError> {
-    let mut archive = tar::Archive::new(body.reader());
+async fn day20_archive_diff(mut multipart: Multipart) -> Result<impl IntoResponse, AppError> {
+    let (mut a, mut b) = (None, None);
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("a") => a = Some(field.bytes().await?),
+            Some("b") => b = Some(field.bytes().await?),
+            _ => continue,
+        }
+    }
+
+    let digests = |name: &str, body: Option<Bytes>| {
+        let body = body.ok_or_else(|| {
+            AppError::bad_request(anyhow::anyhow!("no archive field `{name}` found"))
+        })?;
+        open_archive(body)?.file_digests()
+    };
+    let a = digests("a", a)?;
+    let b = digests("b", b)?;
+
+    let only_in = |x: &BTreeMap<String, String>, y: &BTreeMap<String, String>| {
+        x.keys()
+            .filter(|name| !y.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let changed = a
+        .iter()
+        .filter(|(name, digest)| b.get(*name).is_some_and(|other| other != *digest))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({
+        "only_in_a": only_in(&a, &b),
+        "only_in_b": only_in(&b, &a),
+        "changed": changed,
+    })))
+}
+
+async fn day20_archive_files_list(body: Bytes) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(open_archive(body)?.entries()?))
+}
+
+#[derive(Deserialize)]
+struct CookieSearch {
+    #[serde(default = "CookieSearch::default_branch")]
+    branch: String,
+    #[serde(default = "CookieSearch::default_file")]
+    file: String,
+    #[serde(default = "CookieSearch::default_needle")]
+    needle: String,
+}
+
+impl CookieSearch {
+    fn default_branch() -> String {
+        "christmas".to_string()
+    }
+
+    fn default_file() -> String {
+        "santa.txt".to_string()
+    }
+
+    fn default_needle() -> String {
+        "COOKIE".to_string()
+    }
+}
+
+fn branch_ref(branch: &str) -> Result<String, AppError> {
+    let refname = format!("refs/heads/{branch}");
+    if !git2::Reference::is_valid_name(&refname) {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "invalid branch name: {branch}"
+        )))?
+    }
+    Ok(refname)
+}
+
+// the body is never recorded; only the search parameters that select a commit
+#[tracing::instrument(skip_all, fields(branch = %search.branch, file = %search.file))]
+async fn day20_cookie(Query(search): Query<CookieSearch>, body: Bytes) -> Result<String, AppError> {
+    let refname = branch_ref(&search.branch)?;
+
+    // only bare file names are matched, never paths
+    if std::path::Path::new(&search.file)
+        .file_name()
+        .and_then(|os| os.to_str())
+        != Some(search.file.as_str())
+    {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "invalid file name: {}",
+            search.file
+        )))?
+    }
+
     let dir = tempfile::tempdir()?;
-    archive.unpack(dir.path())?;
+    tracing::info_span!("unpack").in_scope(|| open_archive(body)?.unpack(dir.path()))?;
 
     let repo = git2::Repository::open(dir.path())?;
 
-    let obj = repo.revparse_single("refs/heads/christmas")?;
+    let obj = repo.revparse_single(&refname)?;
 
+    let _revwalk = tracing::info_span!("revwalk").entered();
     let mut rev_walk = repo.revwalk()?;
     rev_walk.push(obj.id())?;
 
@@ -787,12 +2510,14 @@ async fn day20_cookie(body: Bytes) -> Result<String, AppError> {
         let oid = oid?;
         let obj = repo.find_object(oid, None)?;
 
-        repo.checkout_tree(&obj, Some(git2::build::CheckoutBuilder::new().force()))?;
+        tracing::info_span!("checkout", %oid).in_scope(|| {
+            repo.checkout_tree(&obj, Some(git2::build::CheckoutBuilder::new().force()))
+        })?;
 
         for e in walkdir::WalkDir::new(dir.path()) {
             let e = e?;
             let path = e.path();
-            if path.file_name().and_then(|os| os.to_str()) != Some("santa.txt") {
+            if path.file_name().and_then(|os| os.to_str()) != Some(search.file.as_str()) {
                 continue;
             }
 
@@ -800,7 +2525,7 @@ async fn day20_cookie(body: Bytes) -> Result<String, AppError> {
                 continue;
             };
 
-            if !s.contains("COOKIE") {
+            if !s.contains(&search.needle) {
                 continue;
             }
 
@@ -818,208 +2543,3728 @@ async fn day20_cookie(body: Bytes) -> Result<String, AppError> {
     Err(anyhow::anyhow!("no commit found"))?
 }
 
-async fn day21_task1(Path(bin): Path<String>) -> Result<impl IntoResponse, AppError> {
-    let cell_id = s2::cellid::CellID(u64::from_str_radix(&bin, 2)?);
-    let cell = s2::cell::Cell::from(cell_id);
-    let center = cell.center();
-    let lat = center.latitude().deg();
-    let lng = center.longitude().deg();
-
-    let lat = DMS::from_decimal_degrees(lat, true);
-    let lng = DMS::from_decimal_degrees(lng, false);
-
-    let lat = format!(
-        "{}°{}'{:.3}''{}",
-        lat.degrees, lat.minutes, lat.seconds, lat.bearing
-    );
+fn cell_center(bin: &str) -> Result<(f64, f64), AppError> {
+    let cell_id = s2::cellid::CellID(u64::from_str_radix(bin, 2).map_err(AppError::bad_request)?);
+    let center = s2::cell::Cell::from(cell_id).center();
+    Ok((center.latitude().deg(), center.longitude().deg()))
+}
 
-    let lng = format!(
-        "{}°{}'{:.3}''{}",
-        lng.degrees, lng.minutes, lng.seconds, lng.bearing
-    );
+#[derive(Deserialize)]
+struct CommitLogQuery {
+    #[serde(default = "CookieSearch::default_branch")]
+    branch: String,
+    limit: Option<usize>,
+}
 
-    Ok(format!("{lat} {lng}"))
+#[derive(Serialize)]
+struct CommitInfo {
+    id: String,
+    author: String,
+    message: String,
+    time: i64,
 }
 
-async fn day21_task2(Path(bin): Path<String>) -> Result<impl IntoResponse, AppError> {
-    let cell_id = s2::cellid::CellID(u64::from_str_radix(&bin, 2)?);
-    let cell = s2::cell::Cell::from(cell_id);
-    let center = cell.center();
-    let lat = center.latitude().deg();
-    let lng = center.longitude().deg();
+async fn day20_commit_log(
+    Query(query): Query<CommitLogQuery>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let refname = branch_ref(&query.branch)?;
 
-    let cbs = country_boundaries::CountryBoundaries::from_reader(Cursor::new(
-        country_boundaries::BOUNDARIES_ODBL_360X180,
-    ))?;
+    let dir = tempfile::tempdir()?;
+    open_archive(body)?.unpack(dir.path())?;
 
-    let ids = cbs.ids(LatLon::new(lat, lng)?);
+    let repo = git2::Repository::open(dir.path())?;
+    let obj = repo.revparse_single(&refname)?;
 
-    let id = ids
-        .last()
-        .ok_or_else(|| anyhow::anyhow!("no country found"))?;
+    let mut rev_walk = repo.revwalk()?;
+    rev_walk.push(obj.id())?;
 
-    let country = isocountry::CountryCode::for_alpha2(id)?.name();
+    // only commit headers are read; trees and blobs are never touched
+    let commits = rev_walk
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|oid| {
+            let commit = repo.find_commit(oid?)?;
+            let author = commit.author();
+            Ok(CommitInfo {
+                id: commit.id().to_string(),
+                author: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+                message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+                time: commit.time().seconds(),
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
 
-    Ok(country
-        .split_ascii_whitespace()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("no country found"))?)
+    Ok(Json(commits))
 }
 
-async fn day22_task1(body: String) -> Result<impl IntoResponse, AppError> {
-    let nums = body
-        .split_ascii_whitespace()
-        .map(|s| s.parse::<u64>().unwrap())
-        .collect::<Vec<_>>();
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum CoordsFormat {
+    /// `83°39'54.324''N 30°37'40.584''W`, seconds rounded to 3 decimals.
+    #[default]
+    Dms,
+    /// `{"lat": .., "lng": ..}` in unrounded decimal degrees.
+    Decimal,
+}
 
-    let mut ans = 0;
+#[derive(Deserialize)]
+struct CoordsQuery {
+    #[serde(default)]
+    format: CoordsFormat,
+}
 
-    for i in 0..nums.len() {
-        let mut occ = 0;
-        for j in 0..nums.len() {
-            if nums[i] == nums[j] {
-                occ += 1;
-            }
-        }
+async fn day21_task1(
+    Path(bin): Path<String>,
+    Query(query): Query<CoordsQuery>,
+) -> Result<Response, AppError> {
+    let (lat, lng) = cell_center(&bin)?;
 
-        if occ == 1 {
-            ans = nums[i];
-            break;
-        }
+    if let CoordsFormat::Decimal = query.format {
+        return Ok(Json(json!({ "lat": lat, "lng": lng })).into_response());
     }
 
-    let resp = "🎁".repeat(ans as usize);
-    Ok(resp)
+    Ok(format!(
+        "{} {}",
+        format_dms(lat, ['N', 'S']),
+        format_dms(lng, ['E', 'W'])
+    )
+    .into_response())
 }
 
-async fn day22_task2(body: String) -> Result<impl IntoResponse, AppError> {
-    let mut lines = body.lines();
+// `bearings` are the suffixes for non-negative and negative degrees
+fn format_dms(ddeg: f64, bearings: [char; 2]) -> String {
+    let bearing = bearings[(ddeg < 0.0) as usize];
+    let ddeg = ddeg.abs();
 
-    let n = lines.next().unwrap().parse::<usize>().unwrap();
+    let mut degrees = ddeg.trunc() as i32;
+    let mut minutes = ((ddeg - degrees as f64) * 60.0).trunc() as i32;
+    let seconds = (ddeg - degrees as f64 - minutes as f64 / 60.0) * 3600.0;
+    let mut seconds = (seconds * 1000.0).round() / 1000.0;
 
-    let pts = (0..n)
-        .map(|_| {
-            let line = lines.next().unwrap();
-            let nums = line
-                .split_ascii_whitespace()
-                .map(|s| s.parse::<f32>().unwrap())
-                .collect::<Vec<_>>();
-            point3(nums[0], nums[1], nums[2])
-        })
-        .collect::<Vec<Point3D<_>>>();
+    // carry seconds that round up to 60 so we never print 60.000''
+    if seconds >= 60.0 {
+        seconds -= 60.0;
+        minutes += 1;
+    }
+    if minutes >= 60 {
+        minutes -= 60;
+        degrees += 1;
+    }
 
-    let k = lines.next().unwrap().parse::<usize>().unwrap();
+    format!("{degrees}°{minutes}'{seconds:.3}''{bearing}")
+}
 
-    let edges = (0..k)
-        .map(|_| {
-            let line = lines.next().unwrap();
-            let nums = line
-                .split_ascii_whitespace()
-                .map(|s| s.parse::<usize>().unwrap())
-                .collect::<Vec<_>>();
-            (nums[0], nums[1])
-        })
-        .collect::<Vec<(usize, usize)>>();
+#[derive(Deserialize)]
+struct CountryQuery {
+    #[serde(default)]
+    full: bool,
+}
 
-    let mut g = vec![vec![]; n];
+async fn day21_task2(
+    Path(bin): Path<String>,
+    Query(query): Query<CountryQuery>,
+    State(cbs): State<Arc<CountryBoundaries>>,
+) -> Result<impl IntoResponse, AppError> {
+    let (lat, lng) = cell_center(&bin)?;
+    let country = country_at(&cbs, lat, lng)?
+        .ok_or_else(|| anyhow::anyhow!("no country found"))?
+        .name();
 
-    for (u, v) in edges {
-        g[u].push(v);
-        g[v].push(u);
+    if query.full {
+        return Ok(country.to_string());
     }
 
-    let mut q = BinaryHeap::new();
-    q.push(Reverse((0, OrderedFloat(0.0_f32), 0)));
-    let mut done = vec![false; n];
-
-    while let Some(Reverse((dep, OrderedFloat(dist), cur))) = q.pop() {
-        if done[cur] {
-            continue;
-        }
-        done[cur] = true;
+    Ok(country
+        .split_ascii_whitespace()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no country found"))?
+        .to_string())
+}
 
-        if cur == n - 1 {
-            // eprintln!("{dist}");
-            let dist = (dist * 1000.0).round() / 1000.0;
-            // eprintln!("{} {:.3}", dep, dist);
-            return Ok(format!("{} {:.3}", dep, dist));
-        }
+// parsing the embedded dataset is expensive, so it happens once per process
+// and every router shares the result
+fn country_boundaries() -> Arc<CountryBoundaries> {
+    static BOUNDARIES: OnceLock<Arc<CountryBoundaries>> = OnceLock::new();
+    BOUNDARIES
+        .get_or_init(|| {
+            let data = Cursor::new(country_boundaries::BOUNDARIES_ODBL_360X180);
+            Arc::new(CountryBoundaries::from_reader(data).expect("embedded dataset is valid"))
+        })
+        .clone()
+}
 
-        for &next in &g[cur] {
-            if !done[next] {
-                let next_dist = dist + (pts[cur] - pts[next]).length();
-                q.push(Reverse((dep + 1, OrderedFloat(next_dist), next)));
-            }
-        }
-    }
+fn country_at(
+    cbs: &CountryBoundaries,
+    lat: f64,
+    lng: f64,
+) -> Result<Option<isocountry::CountryCode>, AppError> {
+    let ids = cbs.ids(LatLon::new(lat, lng)?);
 
-    Err(anyhow::anyhow!("no route found"))?
+    // ids are ordered from the smallest to the largest area, and subdivisions
+    // such as "US-TX" come before their country; the largest country wins
+    let Some(id) = ids.iter().rev().find(|id| !id.contains('-')) else {
+        return Ok(None);
+    };
+    // the dataset also has user-assigned codes like Kosovo's XK that aren't
+    // part of ISO 3166
+    let country = isocountry::CountryCode::for_alpha2(id)
+        .map_err(|_| AppError::not_found("unknown country", json!({ "country_code": id })))?;
+    Ok(Some(country))
 }
 
-#[derive(Default)]
-struct AppState {
-    day12: HashMap<String, time::Instant>,
+async fn day21_locate(
+    Path(bin): Path<String>,
+    State(cbs): State<Arc<CountryBoundaries>>,
+) -> Result<impl IntoResponse, AppError> {
+    let (lat, lng) = cell_center(&bin)?;
+    let country = country_at(&cbs, lat, lng)?;
+
+    Ok(Json(json!({
+        "country": country.map(|c| c.name()),
+        "country_code": country.map(|c| c.alpha2()),
+        "lat": lat,
+        "lng": lng,
+    })))
 }
 
-#[derive(Clone)]
-struct Pool {
-    pool: PgPool,
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+fn haversine_km((lat1, lng1): (f64, f64), (lat2, lng2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlat = lat2 - lat1;
+    let dlng = (lng2 - lng1).to_radians();
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
 }
 
-#[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::ShuttleAxum {
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .map_err(CustomError::new)?;
+async fn day21_distance(Path((a, b)): Path<(String, String)>) -> Result<String, AppError> {
+    let center = |name: &str, bin: &str| {
+        cell_center(bin).map_err(|_| {
+            AppError::bad_request(anyhow::anyhow!("invalid cell id for {name}: {bin}"))
+        })
+    };
 
-    let shared_state = Arc::new(RwLock::new(AppState::default()));
+    let dist = haversine_km(center("binary_a", &a)?, center("binary_b", &b)?);
+    Ok(format!("{dist:.3}"))
+}
 
-    let router = Router::new()
+// 🎁 is four bytes, so this caps the response at 4 MiB
+const MAX_PRESENTS: u64 = 1 << 20;
+
+async fn day22_task1(body: String) -> Result<impl IntoResponse, AppError> {
+    // every number but one comes in pairs, and pairs cancel out under XOR
+    let mut ans = 0;
+    for (line, line_no) in body.lines().zip(1..) {
+        for s in line.split_ascii_whitespace() {
+            let num = s.parse::<u64>().map_err(|_| {
+                AppError::bad_request(anyhow::anyhow!("line {line_no}: invalid integer {s:?}"))
+            })?;
+            ans ^= num;
+        }
+    }
+
+    if ans > MAX_PRESENTS {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "{ans} presents is more than the sled can carry"
+        )))?
+    }
+
+    let resp = "🎁".repeat(ans as usize);
+    Ok(resp)
+}
+
+struct NumberLines<'a> {
+    lines: std::str::Lines<'a>,
+    line_no: usize,
+}
+
+impl<'a> NumberLines<'a> {
+    fn new(body: &'a str) -> Self {
+        Self {
+            lines: body.lines(),
+            line_no: 0,
+        }
+    }
+
+    fn numbers<T: std::str::FromStr>(&mut self, count: usize) -> Result<Vec<T>, AppError> {
+        self.line_no += 1;
+        let line_no = self.line_no;
+        let bad_request =
+            |msg: String| AppError::bad_request(anyhow::anyhow!("line {line_no}: {msg}"));
+
+        let line = self
+            .lines
+            .next()
+            .ok_or_else(|| bad_request("unexpected end of input".to_string()))?;
+        let nums = line
+            .split_ascii_whitespace()
+            .map(|s| s.parse::<T>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| bad_request(format!("invalid number in {line:?}")))?;
+
+        if nums.len() < count {
+            return Err(bad_request(format!(
+                "expected {count} numbers, found {}",
+                nums.len()
+            )));
+        }
+
+        Ok(nums)
+    }
+}
+
+/// What the rocket route search minimizes first; the other one breaks ties.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum RocketMetric {
+    /// Fewest portal hops, then shortest distance (the original puzzle).
+    #[default]
+    Hops,
+    /// Shortest total Euclidean distance, then fewest hops.
+    Distance,
+}
+
+impl RocketMetric {
+    fn key(self, dep: usize, dist: f32) -> (OrderedFloat<f32>, OrderedFloat<f32>) {
+        match self {
+            RocketMetric::Hops => (OrderedFloat(dep as f32), OrderedFloat(dist)),
+            RocketMetric::Distance => (OrderedFloat(dist), OrderedFloat(dep as f32)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RocketQuery {
+    #[serde(default)]
+    path: bool,
+    #[serde(default)]
+    metric: RocketMetric,
+}
+
+// the stars' positions, and the portals as pairs of star indices
+type RocketMap = (Vec<Point3D<f32>>, Vec<(usize, usize)>);
+
+fn parse_rocket(body: &str) -> Result<RocketMap, AppError> {
+    let mut lines = NumberLines::new(body);
+
+    let n = lines.numbers::<usize>(1)?[0];
+    if n == 0 {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "line 1: no stars given"
+        )))?
+    }
+
+    let pts = (0..n)
+        .map(|_| {
+            let nums = lines.numbers::<f32>(3)?;
+            Ok(point3(nums[0], nums[1], nums[2]))
+        })
+        .collect::<Result<Vec<Point3D<_>>, AppError>>()?;
+
+    let k = lines.numbers::<usize>(1)?[0];
+
+    let edges = (0..k)
+        .map(|_| {
+            let nums = lines.numbers::<usize>(2)?;
+            Ok((nums[0], nums[1]))
+        })
+        .collect::<Result<Vec<(usize, usize)>, AppError>>()?;
+
+    if let Some(&(u, v)) = edges.iter().find(|&&(u, v)| u >= n || v >= n) {
+        Err(AppError::bad_request(anyhow::anyhow!(
+            "portal {u} {v} refers to a star beyond the {n} given"
+        )))?
+    }
+
+    Ok((pts, edges))
+}
+
+async fn day22_rocket_graph(body: String) -> Result<impl IntoResponse, AppError> {
+    let (pts, edges) = parse_rocket(&body)?;
+
+    Ok(Json(json!({
+        "nodes": pts.len(),
+        "edges": edges.iter().map(|&(u, v)| [u, v]).collect::<Vec<_>>(),
+        "points": pts.iter().map(|p| [p.x, p.y, p.z]).collect::<Vec<_>>(),
+    })))
+}
+
+#[tracing::instrument(skip_all, fields(nodes = tracing::field::Empty))]
+async fn day22_task2(Query(query): Query<RocketQuery>, body: String) -> Result<Response, AppError> {
+    let (pts, edges) = parse_rocket(&body)?;
+    let n = pts.len();
+    tracing::Span::current().record("nodes", n);
+
+    let mut g = vec![vec![]; n];
+
+    for (u, v) in edges {
+        g[u].push(v);
+        g[v].push(u);
+    }
+
+    // remaining ties are broken on the star index, so results are deterministic
+    let mut q = BinaryHeap::new();
+    q.push(Reverse((
+        query.metric.key(0, 0.0),
+        0,
+        0,
+        0,
+        OrderedFloat(0.0_f32),
+    )));
+    let mut done = vec![false; n];
+    let mut prev = vec![0; n];
+
+    while let Some(Reverse((_, cur, from, dep, OrderedFloat(dist)))) = q.pop() {
+        if done[cur] {
+            continue;
+        }
+        done[cur] = true;
+        prev[cur] = from;
+
+        if cur == n - 1 {
+            let dist = (dist * 1000.0).round() / 1000.0;
+
+            if !query.path {
+                return Ok(format!("{} {:.3}", dep, dist).into_response());
+            }
+
+            let mut path = vec![cur];
+            while *path.last().unwrap() != 0 {
+                path.push(prev[*path.last().unwrap()]);
+            }
+            path.reverse();
+
+            return Ok(Json(json!({
+                "depth": dep,
+                "distance": dist,
+                "path": path,
+            }))
+            .into_response());
+        }
+
+        for &next in &g[cur] {
+            if !done[next] {
+                let next_dist = dist + (pts[cur] - pts[next]).length();
+                q.push(Reverse((
+                    query.metric.key(dep + 1, next_dist),
+                    next,
+                    cur,
+                    dep + 1,
+                    OrderedFloat(next_dist),
+                )));
+            }
+        }
+    }
+
+    Err(AppError::unprocessable(anyhow::anyhow!(
+        "star {} is unreachable from star 0",
+        n - 1
+    )))
+}
+
+// day12 timers, keyed by name. `load` returns whole seconds since `save`
+trait TimerStore: Send + Sync {
+    fn save(&self, key: &str);
+    fn load(&self, key: &str) -> Option<i64>;
+    fn remove(&self, key: &str) -> bool;
+}
+
+#[derive(Default)]
+struct MemoryTimerStore {
+    timers: RwLock<HashMap<String, Instant>>,
+}
+
+impl TimerStore for MemoryTimerStore {
+    fn save(&self, key: &str) {
+        let mut lock = self.timers.write().unwrap();
+        lock.insert(key.to_string(), Instant::now());
+    }
+
+    fn load(&self, key: &str) -> Option<i64> {
+        let lock = self.timers.read().unwrap();
+        let time = lock.get(key)?;
+        Some(time.elapsed().as_secs_f64().floor() as i64)
+    }
+
+    fn remove(&self, key: &str) -> bool {
+        let mut lock = self.timers.write().unwrap();
+        lock.remove(key).is_some()
+    }
+}
+
+#[derive(Clone)]
+struct Pool {
+    pool: PgPool,
+    reset_lock: Arc<tokio::sync::Mutex<()>>,
+    // latest order total, pushed to /13/ws/totals subscribers
+    totals: Arc<tokio::sync::watch::Sender<i64>>,
+    totals_interval: Duration,
+}
+
+// websocket upgrades (/<day>/ws/...) are long-lived by design, so they are
+// never cut short
+async fn request_timeout(
+    State(timeout): State<Duration>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    if req.uri().path().split('/').nth(2) == Some("ws") {
+        return next.run(req).await;
+    }
+
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(resp) => resp,
+        Err(_) => StatusCode::REQUEST_TIMEOUT.into_response(),
+    }
+}
+
+// ServeDir sends Last-Modified but no ETag, so one is derived from the
+// modification time and length. it's weak since compression may re-encode the body
+async fn asset_etag(mut req: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let if_none_match = req.headers_mut().remove(header::IF_NONE_MATCH);
+    if if_none_match.is_some() {
+        // If-None-Match takes precedence over If-Modified-Since
+        req.headers_mut().remove(header::IF_MODIFIED_SINCE);
+    }
+
+    let mut resp = next.run(req).await;
+    if resp.status() != StatusCode::OK {
+        return resp;
+    }
+    let Some(modified) = resp.headers().get(header::LAST_MODIFIED).cloned() else {
+        return resp;
+    };
+    let len = resp
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    let digest = sha256::digest(format!("{}-{len}", modified.to_str().unwrap_or_default()));
+    let etag = format!("W/\"{}\"", &digest[..16]);
+
+    let matches = if_none_match
+        .as_ref()
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == &etag[2..])
+        });
+
+    let etag = header::HeaderValue::from_str(&etag).unwrap();
+    if matches {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::LAST_MODIFIED, modified)],
+        )
+            .into_response();
+    }
+
+    resp.headers_mut().insert(header::ETAG, etag);
+    resp
+}
+
+fn cors_layer(config: &AppConfig) -> CorsLayer {
+    // anything goes locally; in production only the listed origins are allowed
+    let allow_origin = match &config.cors_origins {
+        Some(origins) => AllowOrigin::list(origins.iter().filter_map(|origin| origin.parse().ok())),
+        None if config.production => AllowOrigin::list([]),
+        None => Any.into(),
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::CONTENT_TYPE])
+}
+
+async fn configure_pool(pool: PgPool, config: &AppConfig) -> Result<PgPool, sqlx::Error> {
+    // reuse the connect options of the provisioned pool, but size it ourselves
+    let options = pool.connect_options().as_ref().clone();
+    pool.close().await;
+
+    let pool = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(config.db_acquire_timeout)
+        .idle_timeout(config.db_idle_timeout)
+        .connect_with(options)
+        .await?;
+
+    // fail fast on bad credentials rather than on the first request
+    sqlx::query("SELECT 1").execute(&pool).await?;
+
+    Ok(pool)
+}
+
+struct GracefulAxum {
+    router: Router,
+    shutdown: CancellationToken,
+}
+
+#[shuttle_runtime::async_trait]
+impl shuttle_runtime::Service for GracefulAxum {
+    async fn bind(self, addr: SocketAddr) -> Result<(), shuttle_runtime::Error> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(CustomError::new)?;
+
+        // the rate limiter falls back to the peer address without a proxy
+        let app = self
+            .router
+            .into_make_service_with_connect_info::<SocketAddr>();
+
+        // cancelling the token lets open websockets send a close frame and exit
+        let shutdown = self.shutdown;
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                shutdown_signal().await;
+                shutdown.cancel();
+            })
+            .await
+            .map_err(CustomError::new)?;
+
+        Ok(())
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[shuttle_runtime::main]
+async fn main(
+    #[shuttle_shared_db::Postgres] pool: PgPool,
+) -> Result<GracefulAxum, shuttle_runtime::Error> {
+    logging::init();
+
+    let config = AppConfig::from_env();
+    let pool = configure_pool(pool, &config)
+        .await
+        .map_err(CustomError::new)?;
+
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .map_err(CustomError::new)?;
+
+    let twitter = TwitterState::new(&config);
+    let shutdown = twitter.shutdown.clone();
+    let router = build_router(pool, twitter, &config);
+
+    Ok(GracefulAxum { router, shutdown })
+}
+
+fn build_router(pool: PgPool, twitter: TwitterState, config: &AppConfig) -> Router {
+    let timers: Arc<dyn TimerStore> = Arc::new(MemoryTimerStore::default());
+    let request_metrics = Arc::new(metrics::Metrics::default());
+    let pokeapi = Arc::new(PokeApi::new(config));
+    let boundaries = country_boundaries();
+
+    let limiter = Arc::new(ratelimit::RateLimiter::new(
+        config.rate_limit_per_sec,
+        config.rate_limit_burst,
+    ));
+
+    // image and archive uploads get their own, larger body limit
+    let uploads = Router::new()
+        .route("/11/red_pixels", post(day11_task2))
+        .route("/11/red_bbox", post(day11_red_bbox))
+        .route("/20/archive_files", post(day20_archive_files))
+        .route("/20/archive_files_size", post(day20_archive_files_size))
+        .route("/20/archive_files_list", post(day20_archive_files_list))
+        .route("/20/archive_diff", post(day20_archive_diff))
+        .route("/20/cookie", post(day20_cookie))
+        .route("/20/commit-log", post(day20_commit_log))
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(config.max_upload_bytes));
+
+    let assets = Router::new()
+        .nest_service("/11/assets", tower_http::services::ServeDir::new("assets"))
+        .layer(axum::middleware::from_fn(asset_etag));
+
+    let router = Router::new()
         .route("/-1/error", get(error))
         .route("/1/*nums", get(day1))
         .route("/4/strength", post(day4_task1))
         .route("/4/contest", post(day4_task2))
         .route("/5", post(day5))
+        .route("/5/explain", get(day5_explain))
         .route("/6", post(day6))
         .route("/7/decode", get(day7_task1))
         .route("/7/bake", get(day7_task2_3))
+        .route("/7/bake-json", post(day7_bake_json))
+        .route("/7/bake-batch", post(day7_bake_batch))
+        .with_state(CookieSigner::new(config))
         .route("/8/weight/:id", get(day8_task1))
         .route("/8/drop/:id", get(day8_task2))
-        .nest_service("/11/assets", tower_http::services::ServeDir::new("assets"))
-        .route("/11/red_pixels", post(day11_task2))
-        .route("/12/save/:key", post(day12_task1_post))
+        .route("/8/weights", post(day8_weights))
+        .route("/8/info/:id", get(day8_info))
+        .with_state(pokeapi)
+        .route("/12/save/:key", post(day12_task1_post).delete(day12_delete))
         .route("/12/load/:key", get(day12_task1_get))
         .route("/12/ulids", post(day12_task2))
+        .route("/12/uuids", post(day12_uuids))
         .route("/12/ulids/:weekday", post(day12_task3))
-        .with_state(shared_state)
+        .route("/12/ulid/:ulid", get(day12_ulid))
+        .with_state(timers)
+        .route("/health", get(health))
         .route("/13/sql", get(day13_task1))
         .route("/13/reset", post(day13_18_reset))
-        .route("/13/orders", post(day13_18_orders))
+        .route(
+            "/13/orders",
+            get(day13_list_orders)
+                .post(day13_18_orders)
+                .delete(day13_truncate_orders),
+        )
         .route("/13/orders/total", get(day13_task2_orders_total))
+        .route(
+            "/13/orders/region/:region_id/total",
+            get(day13_region_total),
+        )
         .route("/13/orders/popular", get(day13_task2_orders_popular))
-        .route("/14/unsafe", post(day14_task1))
-        .route("/14/safe", post(day14_task2))
-        .route("/15/nice", post(day15_task1))
-        .route("/15/game", post(day15_task2))
+        .route(
+            "/13/orders/popular/by_quantity",
+            get(day13_orders_popular_by_quantity),
+        )
+        .route("/13/ws/totals", get(day13_ws_totals))
+        .route("/14/unsafe", post(day14_task1).head(day14_task1_head))
+        .route("/14/safe", post(day14_task2).head(day14_task2_head))
         .route("/18/reset", post(day13_18_reset))
         .route("/18/orders", post(day13_18_orders))
-        .route("/18/regions", post(day18_regions))
+        .route(
+            "/18/regions",
+            post(day18_regions).delete(day18_truncate_regions),
+        )
+        .route("/18/seed", post(day18_seed))
         .route("/18/regions/total", get(day18_total))
         .route("/18/regions/top_list/:limit", get(day18_top_list))
-        .with_state(Pool { pool })
+        .with_state(Pool {
+            pool,
+            reset_lock: Default::default(),
+            totals: Arc::new(tokio::sync::watch::channel(0).0),
+            totals_interval: config.totals_interval,
+        })
+        .route("/15/nice", post(day15_task1))
+        .route("/15/game", post(day15_task2))
+        .route("/15/analyze", post(day15_analyze))
+        .with_state(Arc::new(NiceRules::new(config)))
         .route("/19/ws/ping", get(day19_task1))
+        .route("/19/ws/echo", get(day19_echo))
         .route("/19/reset", post(day19_task2_reset))
         .route("/19/views", get(day19_task2_views))
+        .route("/19/views/:room", get(day19_room_views))
+        .route("/19/rooms", get(day19_rooms))
+        .route("/19/stats", get(day19_stats))
         .route("/19/ws/room/:room_id/user/:user_id", get(day19_task2))
-        .route("/20/archive_files", post(day20_archive_files))
-        .route("/20/archive_files_size", post(day20_archive_files_size))
-        .route("/20/cookie", post(day20_cookie))
+        .route(
+            "/19/room/:room_id/user/:user_id/tweet",
+            post(day19_post_tweet),
+        )
+        .with_state(twitter)
         .route("/21/coords/:binary", get(day21_task1))
         .route("/21/country/:binary", get(day21_task2))
+        .route("/21/locate/:binary", get(day21_locate))
+        .route("/21/distance/:binary_a/:binary_b", get(day21_distance))
         .route("/22/integers", post(day22_task1))
         .route("/22/rocket", post(day22_task2))
-        .with_state(TwitterState::default())
-        .route("/", get(hello_world));
-    Ok(router.into())
+        .route("/22/rocket/graph", post(day22_rocket_graph))
+        .with_state(boundaries)
+        .route("/metrics", get(metrics::scrape))
+        .with_state(request_metrics.clone())
+        .route("/", get(hello_world))
+        .route("/openapi.json", get(openapi::spec))
+        .fallback(not_found)
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+        .merge(uploads)
+        .merge(assets)
+        .layer(axum::middleware::from_fn_with_state(
+            config.request_timeout,
+            request_timeout,
+        ))
+        .layer(CompressionLayer::new().compress_when(
+            // never touch websocket upgrade responses
+            DefaultPredicate::new().and(|status: StatusCode, _: _, _: &_, _: &_| {
+                status != StatusCode::SWITCHING_PROTOCOLS
+            }),
+        ))
+        .layer(cors_layer(config))
+        .layer(axum::middleware::from_fn_with_state(
+            limiter,
+            ratelimit::limit,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            request_metrics,
+            metrics::track,
+        ));
+
+    logging::layer(router)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    fn router() -> Router {
+        router_with(&AppConfig::default())
+    }
+
+    fn router_with(config: &AppConfig) -> Router {
+        // the pool only connects on first use, so handlers that stay away from
+        // the database never need one; the others are ignored unless run
+        // against DATABASE_URL
+        build_router(lazy_pool(), TwitterState::new(config), config)
+    }
+
+    fn lazy_pool() -> PgPool {
+        let url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgres://localhost/cch23".to_string());
+        PgPoolOptions::new().connect_lazy(&url).unwrap()
+    }
+
+    async fn send(router: Router, req: Request<Body>) -> (StatusCode, String) {
+        let resp = router.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    // websockets need a real connection, so serve the router on a free port
+    async fn serve(router: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    // the handshake finishes before the server subscribes to the room, so
+    // tests poll /19/rooms until the count they expect shows up
+    async fn wait_for_subscribers(router: &Router, room: usize, n: usize) {
+        let poll = async {
+            loop {
+                let req = Request::get("/19/rooms").body(Body::empty()).unwrap();
+                let (_, body) = send(router.clone(), req).await;
+                let rooms: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+                let subscribers = rooms
+                    .iter()
+                    .find(|r| r["room"] == room)
+                    .map_or(0, |r| r["subscribers"].as_u64().unwrap());
+                if subscribers == n as u64 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), poll)
+            .await
+            .unwrap();
+    }
+
+    async fn next_json(
+        ws: &mut (impl futures_util::Stream<
+            Item = Result<
+                tokio_tungstenite::tungstenite::Message,
+                tokio_tungstenite::tungstenite::Error,
+            >,
+        > + Unpin),
+    ) -> serde_json::Value {
+        let msg = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        serde_json::from_str(&msg).unwrap()
+    }
+
+    async fn get(uri: &str) -> (StatusCode, String) {
+        send(router(), Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn post_json(uri: &str, body: serde_json::Value) -> (StatusCode, String) {
+        let req = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        send(router(), req).await
+    }
+
+    #[tokio::test]
+    async fn hello_world() {
+        assert_eq!(get("/").await, (StatusCode::OK, "Hello, world!".into()));
+    }
+
+    #[tokio::test]
+    async fn day1_xor_cube() {
+        let expected = (4 ^ 5 ^ 8_i64).pow(3);
+        assert_eq!(
+            get("/1/4/5/8").await,
+            (StatusCode::OK, expected.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn day12_load_unknown_key() {
+        let (status, body) = get("/12/load/never-saved").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "key not found", "status": 404, "key": "never-saved"})
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_past_burst() {
+        let router = router();
+        let hello = || {
+            Request::get("/")
+                .header("x-forwarded-for", "203.0.113.7")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // the bucket refills while we go, so allow some slack past the burst
+        let mut resp = router.clone().oneshot(hello()).await.unwrap();
+        for _ in 0..2 * AppConfig::default().rate_limit_burst as usize {
+            if resp.status() != StatusCode::OK {
+                break;
+            }
+            resp = router.clone().oneshot(hello()).await.unwrap();
+        }
+
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(header::RETRY_AFTER));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_ignores_spoofed_forwarded_for() {
+        let router = router();
+        // only the last hop comes from the proxy; the client picks the rest
+        let hello = |i: usize| {
+            Request::get("/")
+                .header(
+                    "x-forwarded-for",
+                    format!("10.0.{}.{}, 203.0.113.8", i / 256, i % 256),
+                )
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let mut resp = router.clone().oneshot(hello(0)).await.unwrap();
+        for i in 1..2 * AppConfig::default().rate_limit_burst as usize {
+            if resp.status() != StatusCode::OK {
+                break;
+            }
+            resp = router.clone().oneshot(hello(i)).await.unwrap();
+        }
+
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_shares_a_bucket_for_unknown_clients() {
+        let router = router();
+
+        let mut resp = router
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        for _ in 0..2 * AppConfig::default().rate_limit_burst as usize {
+            if resp.status() != StatusCode::OK {
+                break;
+            }
+            resp = router
+                .clone()
+                .oneshot(Request::get("/").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn day12_save_returns_timestamp() {
+        let req = Request::post("/12/save/packet")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["key"], "packet");
+        let saved_at =
+            time::OffsetDateTime::parse(body["saved_at"].as_str().unwrap(), &Rfc3339).unwrap();
+        assert!((time::OffsetDateTime::now_utc() - saved_at).abs() < time::Duration::SECOND);
+    }
+
+    #[tokio::test]
+    async fn day21_locate_land() {
+        let (status, body) =
+            get("/21/locate/0011001000100010010111111111100100110100111110100000010010000101")
+                .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["country_code"], "BN");
+        assert!((body["lat"].as_f64().unwrap() - 4.9).abs() < 1e-6);
+        assert!((body["lng"].as_f64().unwrap() - 114.9).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn day21_locate_ocean() {
+        let (status, body) =
+            get("/21/locate/0111011111110101010101010101111110001000001010101010101010101011")
+                .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["country"], serde_json::Value::Null);
+        assert_eq!(body["country_code"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn day5_zero_split() {
+        let (status, body) = post_json("/5?split=0", json!(["a", "b", "c"])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "split must be >= 1", "status": 400})
+        );
+
+        let (status, _) = get("/5/explain?split=0&len=3").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn day5_oversized_split() {
+        let (status, body) = post_json("/5?split=100", json!(["a", "b", "c"])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([["a", "b", "c"]])
+        );
+    }
+
+    #[tokio::test]
+    async fn day6_large_body() {
+        let phrase = "there is an elf on a shelf next to a shelf. ";
+        let n = 5 * 1024 * 1024 / phrase.len();
+        let req = Request::post("/6")
+            .body(Body::from(phrase.repeat(n)))
+            .unwrap();
+
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "elf": 3 * n,
+                "elf on a shelf": n,
+                "shelf with no elf on it": n,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn day12_decode_ulid() {
+        let (status, body) = get("/12/ulid/01HJDV4GG00000000000000001").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "uuid": "018c9bb2-4200-0000-0000-000000000001",
+                "timestamp_ms": 1703419200000_u64,
+                "datetime_rfc3339": "2023-12-24T12:00:00Z",
+                "weekday": 6,
+                "is_christmas_eve": true,
+                "lsb": true,
+            })
+        );
+    }
+
+    const ARCHIVE_FILES: [(&str, &str); 2] = [("a.txt", "hello"), ("dir/b.txt", "world!")];
+
+    fn tar_archive() -> Vec<u8> {
+        tar_of(&ARCHIVE_FILES)
+    }
+
+    fn tar_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for &(path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn tar_gz_archive() -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar_archive()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zip_archive() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer.add_directory("dir/", Default::default()).unwrap();
+        for (path, content) in ARCHIVE_FILES {
+            writer.start_file(path, Default::default()).unwrap();
+            std::io::Write::write_all(&mut writer, content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn day20_archive_formats_agree() {
+        for archive in [tar_archive(), tar_gz_archive(), zip_archive()] {
+            let req = Request::post("/20/archive_files")
+                .body(Body::from(archive.clone()))
+                .unwrap();
+            assert_eq!(send(router(), req).await, (StatusCode::OK, "2".into()));
+
+            let req = Request::post("/20/archive_files_size")
+                .body(Body::from(archive))
+                .unwrap();
+            assert_eq!(send(router(), req).await, (StatusCode::OK, "11".into()));
+        }
+    }
+
+    // tar::Builder refuses to write `..`, so the name goes into the header raw
+    fn tar_with_raw_entry(header: &mut tar::Header, name: &str, content: &str) -> Vec<u8> {
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(vec![]);
+        builder.append(header, content.as_bytes()).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn day20_unpack_rejects_parent_paths() {
+        let evil = tar_with_raw_entry(&mut tar::Header::new_gnu(), "../evil", "pwned");
+
+        let outer = tempfile::tempdir().unwrap();
+        let dst = outer.path().join("dst");
+        fs::create_dir(&dst).unwrap();
+        let unpacked = open_archive(Bytes::from(evil.clone())).and_then(|a| a.unpack(&dst));
+        assert!(matches!(unpacked, Err(AppError::BadRequest(_))));
+        assert!(!outer.path().join("evil").exists());
+
+        let req = Request::post("/20/cookie").body(Body::from(evil)).unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn day20_unpack_skips_symlinks() {
+        let outer = tempfile::tempdir().unwrap();
+        let dst = outer.path().join("dst");
+        fs::create_dir(&dst).unwrap();
+
+        // a link pointing out of the target, then a file written through it
+        let mut builder = tar::Builder::new(vec![]);
+        let mut link = tar::Header::new_gnu();
+        link.set_entry_type(tar::EntryType::Symlink);
+        link.set_size(0);
+        builder
+            .append_link(&mut link, "link", outer.path())
+            .unwrap();
+        let mut file = tar::Header::new_gnu();
+        file.set_size(5);
+        file.set_cksum();
+        builder
+            .append_data(&mut file, "link/evil", "pwned".as_bytes())
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let unpacked = open_archive(Bytes::from(archive.clone())).and_then(|a| a.unpack(&dst));
+        assert!(unpacked.is_ok());
+        assert!(!outer.path().join("evil").exists());
+        assert!(!dst.join("link").is_symlink());
+
+        // there's no repository in it, so the handler still fails
+        let req = Request::post("/20/cookie")
+            .body(Body::from(archive))
+            .unwrap();
+        assert!(!send(router(), req).await.0.is_success());
+    }
+
+    #[tokio::test]
+    async fn day22_rocket_metrics() {
+        // 0-1-4 takes fewer hops, 0-2-3-4 is the shorter flight
+        let body = "5\n0 0 0\n0 10 0\n1 0 0\n2 0 0\n3 0 0\n5\n0 1\n1 4\n0 2\n2 3\n3 4\n";
+        let rocket = |uri: &str| Request::post(uri).body(Body::from(body)).unwrap();
+
+        assert_eq!(
+            send(router(), rocket("/22/rocket")).await,
+            (StatusCode::OK, "2 20.440".into())
+        );
+        assert_eq!(
+            send(router(), rocket("/22/rocket?metric=distance")).await,
+            (StatusCode::OK, "3 3.000".into())
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn sled_id_of_one_is_its_cube(a in -2_000_000_i64..=2_000_000) {
+            proptest::prop_assert_eq!(sled_id(&[a]), Some(a.pow(3)));
+        }
+
+        #[test]
+        fn sled_id_ignores_order(nums in proptest::collection::vec(-1000_i64..=1000, 0..8)) {
+            let mut reversed = nums.clone();
+            reversed.reverse();
+            let mut sorted = nums.clone();
+            sorted.sort();
+            proptest::prop_assert_eq!(sled_id(&nums), sled_id(&reversed));
+            proptest::prop_assert_eq!(sled_id(&nums), sled_id(&sorted));
+        }
+    }
+
+    fn room_counts(state: &TwitterState) -> Vec<(usize, usize)> {
+        state
+            .rooms()
+            .into_iter()
+            .map(|stats| (stats.room, stats.subscribers))
+            .collect()
+    }
+
+    #[test]
+    fn day19_rooms_prunes_empty_rooms() {
+        let state = TwitterState::new(&AppConfig::default());
+
+        let (_, rx) = state.join(1);
+        assert_eq!(room_counts(&state), [(1, 1)]);
+
+        drop(rx);
+        assert_eq!(room_counts(&state), []);
+        assert!(state.rooms.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn day19_rooms_keeps_history() {
+        let state = TwitterState::new(&AppConfig::default());
+        let tweet = Tweet {
+            user: "elf".into(),
+            message: "hi".into(),
+        };
+
+        let (_, rx) = state.join(1);
+        state.post(1, tweet.clone());
+        drop(rx);
+        assert_eq!(room_counts(&state), []);
+
+        let (history, _rx) = state.join(1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].message, tweet.message);
+    }
+
+    #[tokio::test]
+    async fn day19_echo_numbers_frames() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let addr = serve(router()).await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/echo"))
+            .await
+            .unwrap();
+        for msg in ["hello", "merry", "christmas"] {
+            ws.send(Message::Text(msg.into())).await.unwrap();
+        }
+
+        let mut echoes = vec![];
+        for _ in 0..3 {
+            echoes.push(ws.next().await.unwrap().unwrap().into_text().unwrap());
+        }
+        assert_eq!(echoes, ["1: hello", "2: merry", "3: christmas"]);
+
+        ws.close(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_sql_expression() {
+        assert_eq!(
+            get("/13/sql?expr=2%2B2*10").await,
+            (StatusCode::OK, "22".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn day13_sql_rejects_injection() {
+        let (status, _) = get("/13/sql?expr=1;DROP%20TABLE%20orders").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn day4_contest_by_field() {
+        let reindeer = json!([
+            {"name": "Dasher", "strength": 5, "speed": 50.4},
+            {"name": "Dancer", "strength": 6, "speed": 48.2},
+        ]);
+
+        let (status, body) = post_json("/4/contest?by=strength", reindeer.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"winner": "Dancer", "value": 6})
+        );
+
+        let (status, _) = post_json("/4/contest?by=antlers", reindeer).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for uri in ["/4/contest?by=speed", "/4/contest"] {
+            let (status, body) = post_json(uri, json!([])).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"error": "no reindeer given", "status": 400})
+            );
+        }
+    }
+
+    fn multipart(body: &str) -> Request<Body> {
+        Request::post("/11/red_pixels")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body.replace('\n', "\r\n")))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn day11_empty_multipart() {
+        let (status, _) = send(router(), multipart("--X--\n")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn day11_text_as_image() {
+        let body = "--X\n\
+            Content-Disposition: form-data; name=\"image\"; filename=\"a.png\"\n\
+            Content-Type: text/plain\n\
+            \n\
+            not a png\n\
+            --X--\n";
+        let (status, _) = send(router(), multipart(body)).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn day20_commit_log() {
+        let jar = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/cookiejar.tar")).unwrap();
+        let req = Request::post("/20/commit-log?branch=christmas")
+            .body(Body::from(jar))
+            .unwrap();
+
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let commits: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(commits.len(), 8);
+        assert_eq!(commits[0]["id"], "38877b0f58625b19defeb7263fca8eacc2cfa96f");
+        assert_eq!(commits[0]["author"], "Rudolph");
+    }
+
+    #[tokio::test]
+    async fn day14_serves_html() {
+        for uri in ["/14/unsafe", "/14/safe"] {
+            let req = Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({"content": "<h1>hi</h1>"}).to_string()))
+                .unwrap();
+            let resp = router().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers()[header::CONTENT_TYPE],
+                "text/html; charset=utf-8"
+            );
+
+            let req = Request::head(uri).body(Body::empty()).unwrap();
+            let resp = router().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers()[header::CONTENT_TYPE],
+                "text/html; charset=utf-8"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn day15_extra_forbidden_pair() {
+        let nice = |router: Router| async move {
+            let req = Request::post("/15/nice")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({"input": "jazzy hello"}).to_string()))
+                .unwrap();
+            send(router, req).await.0
+        };
+        assert_eq!(nice(router()).await, StatusCode::OK);
+
+        let mut config = AppConfig::default();
+        config.nice_forbidden.push("zz".into());
+        assert_eq!(nice(router_with(&config)).await, StatusCode::BAD_REQUEST);
+    }
+
+    async fn delete(router: Router, uri: &str) -> StatusCode {
+        let req = Request::delete(uri).body(Body::empty()).unwrap();
+        send(router, req).await.0
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_18_truncate() {
+        let router = router();
+        reset_and_seed(
+            &router,
+            json!({
+                "regions": [{"id": 1, "name": "Pole"}],
+                "orders": [{"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 3}],
+            }),
+        )
+        .await;
+
+        let total = || async {
+            let req = Request::get("/13/orders/total")
+                .body(Body::empty())
+                .unwrap();
+            let (_, body) = send(router.clone(), req).await;
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        };
+
+        assert_eq!(
+            delete(router.clone(), "/13/orders").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(total().await, json!({"total": 0}));
+
+        // the region is still there; truncating it takes the orders along
+        let orders = json!([{"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 3}]);
+        let req = Request::post("/13/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(orders.to_string()))
+            .unwrap();
+        assert_eq!(send(router.clone(), req).await.0, StatusCode::OK);
+        assert_eq!(total().await, json!({"total": 3}));
+
+        assert_eq!(
+            delete(router.clone(), "/18/regions").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(total().await, json!({"total": 0}));
+        let req = Request::get("/18/regions/total")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router.clone(), req).await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn day5_ndjson() {
+        let names = json!(["a", "b", "c", "d", "e"]);
+
+        let (status, body) = post_json("/5?offset=1&format=ndjson", names.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.lines().count(), 4);
+        assert_eq!(body.lines().next(), Some("\"b\""));
+
+        let (_, body) = post_json("/5?split=2&format=ndjson", names).await;
+        assert_eq!(body, "[\"a\",\"b\"]\n[\"c\",\"d\"]\n[\"e\"]\n");
+    }
+
+    #[tokio::test]
+    async fn day19_views_per_room() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let join = |room: usize, user: &str| {
+            let url = format!("ws://{addr}/19/ws/room/{room}/user/{user}");
+            async move { tokio_tungstenite::connect_async(url).await.unwrap().0 }
+        };
+
+        let mut alice = join(1, "alice").await;
+        let mut bob = join(1, "bob").await;
+        let mut carol = join(2, "carol").await;
+
+        wait_for_subscribers(&router, 1, 2).await;
+        wait_for_subscribers(&router, 2, 1).await;
+
+        let tweet = Message::Text(json!({"message": "hi"}).to_string());
+        alice.send(tweet.clone()).await.unwrap();
+        carol.send(tweet).await.unwrap();
+
+        // each delivery is counted before it is sent, so seeing it is enough
+        for ws in [&mut alice, &mut bob, &mut carol] {
+            ws.next().await.unwrap().unwrap();
+        }
+
+        let views = |uri: &str| {
+            let req = Request::get(uri).body(Body::empty()).unwrap();
+            send(router.clone(), req)
+        };
+        assert_eq!(views("/19/views/1").await.1, "2");
+        assert_eq!(views("/19/views/2").await.1, "1");
+        assert_eq!(views("/19/views").await.1, "3");
+    }
+
+    #[tokio::test]
+    async fn day22_integers_many() {
+        let mut body = (1..500_000_u64)
+            .flat_map(|n| [n, n])
+            .map(|n| format!("{n}\n"))
+            .collect::<String>();
+        body.push_str("7\n");
+
+        let req = Request::post("/22/integers")
+            .body(Body::from(body))
+            .unwrap();
+        assert_eq!(send(router(), req).await, (StatusCode::OK, "🎁".repeat(7)));
+    }
+
+    #[tokio::test]
+    async fn day22_integers_too_many_presents() {
+        let req = Request::post("/22/integers")
+            .body(Body::from("18446744073709551615\n"))
+            .unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn app_error_is_json() {
+        let (status, body) = get("/1/4/sled").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "invalid digit found in string", "status": 400})
+        );
+    }
+
+    #[tokio::test]
+    async fn day19_drops_unresponsive_clients() {
+        let config = AppConfig {
+            ws_ping_interval: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let router = router_with(&config);
+        let addr = serve(router.clone()).await;
+
+        // never reading means the client never answers the server's pings
+        let (_ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/room/1/user/idle"))
+                .await
+                .unwrap();
+
+        wait_for_subscribers(&router, 1, 1).await;
+        wait_for_subscribers(&router, 1, 0).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_total_min_total() {
+        let router = router();
+        reset_and_seed(
+            &router,
+            json!({
+                "regions": [{"id": 1, "name": "North"}, {"id": 2, "name": "South"}],
+                "orders": [
+                    {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 10},
+                    {"id": 2, "region_id": 2, "gift_name": "Sock", "quantity": 2},
+                ],
+            }),
+        )
+        .await;
+
+        let req = Request::get("/18/regions/total?min_total=5")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router, req).await;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([{"region": "North", "total": 10}])
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_seed_rolls_back() {
+        let router = router();
+        reset(&router).await;
+
+        // gift_name is a VARCHAR(50), so the orders insert fails
+        let seed = json!({
+            "regions": [{"id": 1, "name": "North"}],
+            "orders": [{"id": 1, "region_id": 1, "gift_name": "x".repeat(51), "quantity": 1}],
+        });
+        let req = Request::post("/18/seed")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(seed.to_string()))
+            .unwrap();
+        assert_eq!(
+            send(router.clone(), req).await.0,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        // had the region survived, this order would show up under it
+        let orders = json!([{"id": 2, "region_id": 1, "gift_name": "Sled", "quantity": 3}]);
+        let req = Request::post("/13/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(orders.to_string()))
+            .unwrap();
+        assert_eq!(send(router.clone(), req).await.0, StatusCode::OK);
+
+        let req = Request::get("/18/regions/total")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router, req).await;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([])
+        );
+    }
+
+    #[tokio::test]
+    async fn day12_weekday_forms() {
+        let ulids = json!(["01HJDV4GG00000000000000001"]);
+
+        let (status, body) = post_json("/12/ulids/7", ulids.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "weekday must be 0..=6", "status": 400})
+        );
+
+        // 2023-12-24 was a Sunday
+        for weekday in ["6", "Sunday", "sunday"] {
+            let (status, body) = post_json(&format!("/12/ulids/{weekday}"), ulids.clone()).await;
+            assert_eq!(status, StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["weekday"], 1);
+        }
+
+        for weekday in ["3", "Wednesday"] {
+            let (status, body) = post_json(&format!("/12/ulids/{weekday}"), ulids.clone()).await;
+            assert_eq!(status, StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["weekday"], 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_requests_time_out() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            "done"
+        };
+        let router = Router::new()
+            .route("/slow", axum::routing::get(slow))
+            .route("/19/ws/slow", axum::routing::get(slow))
+            .layer(axum::middleware::from_fn_with_state(
+                Duration::from_millis(50),
+                request_timeout,
+            ));
+
+        let (status, _) = send(
+            router.clone(),
+            Request::get("/slow").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+
+        let ws = router.oneshot(Request::get("/19/ws/slow").body(Body::empty()).unwrap());
+        assert!(tokio::time::timeout(Duration::from_millis(200), ws)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn day21_distance() {
+        // Brunei (4.9, 114.9) to the mid-Atlantic (0, -30)
+        let brunei = "0011001000100010010111111111100100110100111110100000010010000101";
+        let ocean = "0000100010011110000000101001111111010101010110001010101010000001";
+        assert_eq!(
+            get(&format!("/21/distance/{brunei}/{ocean}")).await,
+            (StatusCode::OK, "16079.136".into())
+        );
+
+        let (status, body) = get(&format!("/21/distance/{brunei}/12")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("binary_b"));
+    }
+
+    #[tokio::test]
+    async fn day19_truncates_long_tweets() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let addr = serve(router()).await;
+        let url = format!("ws://{addr}/19/ws/room/1/user/long?truncate=true");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        let message = "ab".repeat(100);
+        ws.send(Message::Text(json!({ "message": message }).to_string()))
+            .await
+            .unwrap();
+
+        let tweet = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        let tweet: serde_json::Value = serde_json::from_str(&tweet).unwrap();
+        assert_eq!(tweet["message"], message[..128]);
+    }
+
+    #[tokio::test]
+    async fn day8_info_from_mock_pokeapi() {
+        let pikachu = || async {
+            Json(json!({
+                "name": "pikachu",
+                "weight": 60,
+                "height": 4,
+                "types": [{"slot": 1, "type": {"name": "electric", "url": ""}}],
+            }))
+        };
+        let mock = serve(Router::new().route("/pokemon/25/", axum::routing::get(pikachu))).await;
+
+        let config = AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        };
+        let req = Request::get("/8/info/25").body(Body::empty()).unwrap();
+        let (status, body) = send(router_with(&config), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "id": 25,
+                "name": "pikachu",
+                "weight_kg": 6.0,
+                "types": ["electric"],
+                "height": 4,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn day20_archive_files_gnu_tar() {
+        // made with `tar --format=gnu`; one name is long enough to need a
+        // GNU longname header
+        let archive = include_bytes!("../assets/gnu-longname.tar").as_slice();
+
+        let req = Request::post("/20/archive_files")
+            .body(Body::from(archive))
+            .unwrap();
+        assert_eq!(send(router(), req).await, (StatusCode::OK, "3".into()));
+
+        let req = Request::post("/20/archive_files_size")
+            .body(Body::from(archive))
+            .unwrap();
+        assert_eq!(send(router(), req).await, (StatusCode::OK, "15".into()));
+    }
+
+    #[tokio::test]
+    async fn day22_rocket_graph_rejects_unknown_star() {
+        let graph = |body: &'static str| {
+            Request::post("/22/rocket/graph")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let (status, body) = send(router(), graph("2\n0 0 0\n3 4 0\n1\n0 1\n")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({ "nodes": 2, "edges": [[0, 1]], "points": [[0.0, 0.0, 0.0], [3.0, 4.0, 0.0]] })
+        );
+
+        let (status, _) = send(router(), graph("2\n0 0 0\n3 4 0\n1\n0 2\n")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let req = Request::post("/22/rocket")
+            .body(Body::from("2\n0 0 0\n3 4 0\n1\n0 2\n"))
+            .unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn json_rejections_explain_the_problem() {
+        let req = Request::post("/4/strength")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(r#"[{"name": "Dasher", "strength": 5}]"#))
+            .unwrap();
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "expected `Content-Type: application/json`");
+        assert!(body["detail"].is_string());
+
+        let req = Request::post("/4/strength")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"[{"name": "Dasher", "strength": 5}"#))
+            .unwrap();
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "body is not valid JSON");
+
+        let (status, body) = post_json("/4/strength", json!([{"name": "Dasher"}])).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "JSON does not match the expected shape");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_region_total() {
+        let router = router();
+        reset_and_seed(
+            &router,
+            json!({
+                "regions": [{"id": 1, "name": "North"}, {"id": 2, "name": "South"}],
+                "orders": [
+                    {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 10},
+                    {"id": 2, "region_id": 1, "gift_name": "Sock", "quantity": 2},
+                ],
+            }),
+        )
+        .await;
+
+        for (region_id, total) in [(1, 12), (2, 0)] {
+            let req = Request::get(format!("/13/orders/region/{region_id}/total"))
+                .body(Body::empty())
+                .unwrap();
+            let (status, body) = send(router.clone(), req).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"region_id": region_id, "total": total})
+            );
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanNames {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _: &tracing::span::Id,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.0.lock().unwrap().push(attrs.metadata().name());
+        }
+    }
+
+    #[tokio::test]
+    async fn day8_handler_span() {
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let spans = SpanNames::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let pikachu = || async { Json(json!({ "name": "pikachu", "weight": 60 })) };
+        let mock = serve(Router::new().route("/pokemon/25/", axum::routing::get(pikachu))).await;
+        let config = AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        };
+        let req = Request::get("/8/weight/25").body(Body::empty()).unwrap();
+        assert_eq!(
+            send(router_with(&config), req).await,
+            (StatusCode::OK, "6".into())
+        );
+
+        assert!(spans.0.lock().unwrap().contains(&"day8_task1"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_orders_insert_is_idempotent() {
+        let router = router();
+        assert_eq!(
+            delete(router.clone(), "/13/orders").await,
+            StatusCode::NO_CONTENT
+        );
+
+        let order = |id| json!({"id": id, "region_id": 1, "gift_name": "Sled", "quantity": 1});
+        let insert = |ids: &[i32]| {
+            Request::post("/13/orders")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!(ids.iter().map(|&id| order(id)).collect::<Vec<_>>()).to_string(),
+                ))
+                .unwrap()
+        };
+
+        for (ids, inserted) in [(&[1, 2][..], 2), (&[1, 2][..], 0), (&[2, 3][..], 1)] {
+            let (status, body) = send(router.clone(), insert(ids)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"inserted": inserted})
+            );
+        }
+
+        let req = Request::get("/13/orders/total")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router, req).await;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"total": 3})
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_ws_totals_follow_inserts() {
+        let router = router();
+        assert_eq!(
+            delete(router.clone(), "/13/orders").await,
+            StatusCode::NO_CONTENT
+        );
+
+        let addr = serve(router.clone()).await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/13/ws/totals"))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut ws).await["total"], 0);
+
+        let orders = json!([{"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 7}]);
+        let req = Request::post("/13/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(orders.to_string()))
+            .unwrap();
+        assert_eq!(send(router, req).await.0, StatusCode::OK);
+
+        // a periodic tick may still report the old total first
+        let total = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let total = next_json(&mut ws).await["total"].clone();
+                if total != 0 {
+                    return total;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(total, 7);
+    }
+
+    #[tokio::test]
+    async fn day15_analyze_reports_every_rule() {
+        // too short and no digits, yet joyful with a sandwich
+        let (status, body) = post_json("/15/analyze", json!({"input": "joyAbA"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "result": "naughty",
+                "rules": {
+                    "length": false,
+                    "char_types": false,
+                    "digit_count": false,
+                    "sum": false,
+                    "joy": true,
+                    "sandwich": true,
+                    "unicode_range": false,
+                    "emoji": false,
+                    "sha_suffix": false,
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn day11_assets_revalidate_with_etag() {
+        let req = Request::get("/11/assets/decoration.png")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().contains_key(header::LAST_MODIFIED));
+        let etag = resp.headers()[header::ETAG].clone();
+
+        let req = Request::get("/11/assets/decoration.png")
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap();
+        let resp = router().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers()[header::ETAG], etag);
+
+        let req = Request::get("/11/assets/decoration.png")
+            .header(header::IF_NONE_MATCH, "W/\"stale\"")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn day19_tweet_over_http() {
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/room/3/user/bob"))
+                .await
+                .unwrap();
+
+        wait_for_subscribers(&router, 3, 1).await;
+
+        let tweet = |message: String| {
+            Request::post("/19/room/3/user/curl/tweet")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "message": message }).to_string()))
+                .unwrap()
+        };
+        assert_eq!(
+            send(router.clone(), tweet("a".repeat(129))).await.0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            send(router.clone(), tweet("hi".into())).await.0,
+            StatusCode::ACCEPTED
+        );
+
+        assert_eq!(
+            next_json(&mut ws).await,
+            json!({"user": "curl", "message": "hi"})
+        );
+
+        let req = Request::get("/19/views/3").body(Body::empty()).unwrap();
+        assert_eq!(send(router, req).await.1, "1");
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_json_404() {
+        let (status, body) = get("/does/not/exist").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "no such route", "status": 404, "path": "/does/not/exist"})
+        );
+    }
+
+    #[tokio::test]
+    async fn day21_coords_decimal() {
+        let cell = "0100111110010011000110011001010101011111000010100011110001011011";
+
+        assert_eq!(
+            get(&format!("/21/coords/{cell}")).await,
+            (StatusCode::OK, "83°39'54.324''N 30°37'40.584''W".into())
+        );
+
+        let (status, body) = get(&format!("/21/coords/{cell}?format=decimal")).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        // well below the DMS output's 0.001'' (about 3e-7 degrees)
+        assert!((body["lat"].as_f64().unwrap() - 83.6650899838655).abs() < 1e-9);
+        assert!((body["lng"].as_f64().unwrap() - -30.627939871985497).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn day19_lagging_subscriber_is_warned() {
+        let config = AppConfig {
+            room_capacity: 2,
+            ..Default::default()
+        };
+        let twitter = TwitterState::new(&config);
+        let router = build_router(lazy_pool(), twitter.clone(), &config);
+        let addr = serve(router.clone()).await;
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/room/4/user/slow"))
+                .await
+                .unwrap();
+
+        wait_for_subscribers(&router, 4, 1).await;
+
+        // nothing else runs in between on the test's single thread, so the
+        // socket task falls behind by everything but the last two
+        let tweet = |message: &str| Tweet {
+            user: "fast".into(),
+            message: message.into(),
+        };
+        for i in 0..10 {
+            twitter.post(4, tweet(&i.to_string()));
+        }
+
+        assert_eq!(
+            next_json(&mut ws).await,
+            json!({"warning": "dropped", "count": 8})
+        );
+        assert_eq!(next_json(&mut ws).await["message"], "8");
+        assert_eq!(next_json(&mut ws).await["message"], "9");
+
+        twitter.post(4, tweet("still here"));
+        assert_eq!(next_json(&mut ws).await["message"], "still here");
+    }
+
+    #[tokio::test]
+    async fn day7_bake_batch_shares_pantry() {
+        let (status, body) = post_json(
+            "/7/bake-batch",
+            json!({
+                "pantry": {"flour": 100, "sugar": 20},
+                "recipes": [
+                    {"name": "plain", "ingredients": {"flour": 10}},
+                    {"name": "sweet", "ingredients": {"flour": 20, "sugar": 5}},
+                ],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([
+                {"name": "plain", "cookies": 10},
+                {"name": "sweet", "cookies": 4},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn day20_archive_diff() {
+        let a = tar_of(&[("same.txt", "x"), ("grown.txt", "abc"), ("gone.txt", "bye")]);
+        let b = tar_of(&[
+            ("same.txt", "x"),
+            ("grown.txt", "abcdef"),
+            ("new.txt", "hi"),
+        ]);
+
+        let mut body = vec![];
+        for (name, tar) in [("a", a), ("b", b)] {
+            body.extend_from_slice(
+                format!(
+                    "--X\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}.tar\"\r\n\
+                     Content-Type: application/x-tar\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(&tar);
+            body.extend_from_slice(b"\r\n");
+        }
+        let req = |body: Vec<u8>| {
+            Request::post("/20/archive_diff")
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let (status, resp) = send(router(), req([body.as_slice(), b"--X--\r\n"].concat())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&resp).unwrap(),
+            json!({
+                "only_in_a": ["gone.txt"],
+                "only_in_b": ["new.txt"],
+                "changed": ["grown.txt"],
+            })
+        );
+
+        // only the first field
+        let first = body.windows(3).rposition(|w| w == b"--X").unwrap();
+        let (status, _) = send(router(), req([&body[..first], b"--X--\r\n"].concat())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn day19_rejects_control_characters() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let addr = serve(router()).await;
+        let url = format!("ws://{addr}/19/ws/room/1/user/bell");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        for message in ["ding\u{0007}", "ding"] {
+            ws.send(Message::Text(json!({ "message": message }).to_string()))
+                .await
+                .unwrap();
+        }
+
+        let mut frames = vec![];
+        for _ in 0..2 {
+            let frame = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            frames.push(serde_json::from_str::<serde_json::Value>(&frame).unwrap());
+        }
+        // the rejected message doesn't cost the socket its connection
+        assert_eq!(
+            frames,
+            [
+                json!({"error": "control_characters"}),
+                json!({"user": "bell", "message": "ding"}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn day19_tweet_length_counts_graphemes() {
+        // seven chars, one grapheme
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let tweet = |message: String| {
+            Request::post("/19/room/1/user/emoji/tweet")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "message": message }).to_string()))
+                .unwrap()
+        };
+
+        let fits = "a".repeat(127) + family;
+        assert_eq!(send(router(), tweet(fits)).await.0, StatusCode::ACCEPTED);
+
+        let too_long = "a".repeat(127) + family + family;
+        let (status, body) = send(router(), tweet(too_long)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "message_too_long", "max": 128})
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_popular_by_quantity() {
+        let router = router();
+        let popular = || {
+            let req = Request::get("/13/orders/popular/by_quantity")
+                .body(Body::empty())
+                .unwrap();
+            send(router.clone(), req)
+        };
+        let insert = |orders: serde_json::Value| {
+            let req = Request::post("/13/orders")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(orders.to_string()))
+                .unwrap();
+            send(router.clone(), req)
+        };
+        let parse = |body: String| serde_json::from_str::<serde_json::Value>(&body).unwrap();
+
+        assert_eq!(
+            delete(router.clone(), "/13/orders").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(parse(popular().await.1), json!({"popular": null}));
+
+        insert(json!([
+            {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 2},
+            {"id": 2, "region_id": 1, "gift_name": "Sled", "quantity": 3},
+            {"id": 3, "region_id": 1, "gift_name": "Sock", "quantity": 4},
+        ]))
+        .await;
+        assert_eq!(parse(popular().await.1), json!({"popular": "Sled"}));
+
+        insert(json!([{"id": 4, "region_id": 1, "gift_name": "Sock", "quantity": 1}])).await;
+        assert_eq!(
+            parse(popular().await.1),
+            json!({"popular": ["Sled", "Sock"]})
+        );
+    }
+
+    #[tokio::test]
+    async fn day5_explain_window() {
+        let (status, body) = get("/5/explain?offset=2&limit=10&len=5").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "offset": 2,
+                "limit": 10,
+                "split": null,
+                "effective_start": 2,
+                "effective_end": 5,
+            })
+        );
+
+        let (_, body) = post_json("/5?offset=7", json!(["a", "b"])).await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn day7_signed_recipe_cookie() {
+        let b64 = |bytes: &[u8]| base64::prelude::BASE64_STANDARD.encode(bytes);
+        let bake = |router: Router, cookie: String| {
+            let req = Request::get("/7/bake")
+                .header(header::COOKIE, format!("recipe={cookie}"))
+                .body(Body::empty())
+                .unwrap();
+            send(router, req)
+        };
+        let payload = json!({"recipe": {"flour": 10}, "pantry": {"flour": 25}}).to_string();
+        let baked = json!({"cookies": 2, "pantry": {"flour": 5}});
+
+        // without a key, plain cookies are still accepted
+        let (status, body) = bake(router(), b64(payload.as_bytes())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            baked
+        );
+
+        let config = AppConfig {
+            recipe_cookie_key: Some("secret".into()),
+            ..Default::default()
+        };
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(payload.as_bytes());
+        let signature = b64(&mac.finalize().into_bytes());
+
+        let signed = format!("{}.{signature}", b64(payload.as_bytes()));
+        let (status, body) = bake(router_with(&config), signed).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            baked
+        );
+
+        let forged = payload.replace("25", "2500");
+        let tampered = format!("{}.{signature}", b64(forged.as_bytes()));
+        assert_eq!(
+            bake(router_with(&config), tampered).await.0,
+            StatusCode::BAD_REQUEST
+        );
+
+        // nor is an unsigned one once a key is set
+        assert_eq!(
+            bake(router_with(&config), b64(payload.as_bytes())).await.0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    fn nice_rules() -> NiceRules {
+        // every rule switched off, so each test can turn on just one
+        NiceRules {
+            min_vowels: 0,
+            require_double: false,
+            forbidden: vec![],
+            ..NiceRules::default()
+        }
+    }
+
+    #[test]
+    fn nice_rules_default() {
+        assert!(is_nice("hello there"));
+        assert!(!is_nice("abcd"));
+        assert!(!is_nice("hello xylophone"));
+        assert!(!is_nice("helo there"));
+
+        // the unconfigured AppConfig reproduces the stock rules
+        let configured = NiceRules::new(&AppConfig::default());
+        let stock = NiceRules::default();
+        assert_eq!(configured.vowels, stock.vowels);
+        assert_eq!(configured.forbidden, stock.forbidden);
+    }
+
+    #[test]
+    fn nice_rules_min_vowels() {
+        let rules = NiceRules {
+            min_vowels: 3,
+            ..nice_rules()
+        };
+        assert!(rules.evaluate("aei"));
+        assert!(!rules.evaluate("aex"));
+        assert!(nice_rules().evaluate("xyz"));
+    }
+
+    #[test]
+    fn nice_rules_require_double() {
+        let rules = NiceRules {
+            require_double: true,
+            ..nice_rules()
+        };
+        assert!(rules.evaluate("hello"));
+        assert!(!rules.evaluate("helo"));
+        // doubled punctuation doesn't count
+        assert!(!rules.evaluate("he..o"));
+    }
+
+    #[test]
+    fn nice_rules_forbidden() {
+        let config = AppConfig {
+            nice_forbidden: vec!["zz".into()],
+            ..AppConfig::default()
+        };
+        let rules = NiceRules {
+            forbidden: NiceRules::new(&config).forbidden,
+            ..nice_rules()
+        };
+        assert!(!rules.evaluate("jazzy"));
+        assert!(rules.evaluate("abcd"));
+        assert!(nice_rules().evaluate("jazzy"));
+    }
+
+    #[tokio::test]
+    async fn day12_uuids_round_trip() {
+        let ulids = json!([
+            "01BJQ0E1C3Z56ABCD0E11HYX4M",
+            "01BJQ0E1C3Z56ABCD0E11HYX5N",
+            "01BJQ0E1C3Z56ABCD0E11HYX6Q",
+        ]);
+
+        let (status, uuids) = post_json("/12/ulids", ulids.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // both directions reverse, so two trips restore the order
+        let uuids: serde_json::Value = serde_json::from_str(&uuids).unwrap();
+        let (status, body) = post_json("/12/uuids", uuids).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            ulids
+        );
+
+        let (status, body) = post_json("/12/uuids", json!(["not-a-uuid"])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("not-a-uuid"));
+    }
+
+    async fn reset(router: &Router) {
+        let req = Request::post("/18/reset").body(Body::empty()).unwrap();
+        assert_eq!(send(router.clone(), req).await.0, StatusCode::OK);
+    }
+
+    async fn reset_and_seed(router: &Router, seed: serde_json::Value) {
+        reset(router).await;
+
+        let req = Request::post("/18/seed")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(seed.to_string()))
+            .unwrap();
+        assert_eq!(send(router.clone(), req).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_top_list_region_without_orders() {
+        let router = router();
+        let seed = json!({
+            "regions": [{"id": 1, "name": "North"}, {"id": 2, "name": "X"}],
+            "orders": [{"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 3}],
+        });
+        reset_and_seed(&router, seed).await;
+
+        let req = Request::get("/18/regions/top_list/2")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router, req).await;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([
+                {"region": "North", "top_gifts": ["Sled"]},
+                {"region": "X", "top_gifts": []},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_top_list_region_window() {
+        let router = router();
+        let seed = json!({
+            "regions": [
+                {"id": 1, "name": "C"},
+                {"id": 2, "name": "A"},
+                {"id": 3, "name": "B"},
+            ],
+            "orders": [],
+        });
+        reset_and_seed(&router, seed).await;
+
+        let regions = |query: &'static str| {
+            let router = router.clone();
+            async move {
+                let uri = format!("/18/regions/top_list/1?{query}");
+                let (_, body) = send(router, Request::get(uri).body(Body::empty()).unwrap()).await;
+                serde_json::from_str::<Vec<serde_json::Value>>(&body)
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| r["region"].as_str().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(regions("region_offset=1&region_limit=1").await, ["B"]);
+        assert_eq!(regions("region_offset=1").await, ["B", "C"]);
+        assert!(regions("region_offset=3").await.is_empty());
+        assert!(regions("region_offset=10&region_limit=2").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn day15_game_verbose_rule() {
+        // long enough, mixed, six digits, but 2000 + 24 is not 2023
+        let input = json!({"input": "2000.24.A j  ;) o  ;) y"});
+
+        let (status, body) = post_json("/15/game?verbose=true", input.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"result": "naughty", "reason": "math is hard", "rule": 4})
+        );
+
+        let (status, body) = post_json("/15/game", input).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"result": "naughty", "reason": "math is hard"})
+        );
+    }
+
+    #[tokio::test]
+    async fn day15_game_long_digit_runs() {
+        // 30 digits overflow i64, 45 overflow even i128
+        for run in [30, 45] {
+            let input = json!({"input": format!("Ab{}", "9".repeat(run))});
+            let (status, body) = post_json("/15/game", input).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"result": "naughty", "reason": "math is hard"})
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn day19_joiners_get_the_backlog() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let join = |user: &str| {
+            let url = format!("ws://{addr}/19/ws/room/5/user/{user}");
+            async move { tokio_tungstenite::connect_async(url).await.unwrap().0 }
+        };
+
+        let mut alice = join("alice").await;
+        wait_for_subscribers(&router, 5, 1).await;
+        alice
+            .send(Message::Text(json!({"message": "hi"}).to_string()))
+            .await
+            .unwrap();
+        let tweet = json!({"user": "alice", "message": "hi"});
+        assert_eq!(next_json(&mut alice).await, tweet);
+
+        let mut bob = join("bob").await;
+        assert_eq!(next_json(&mut bob).await, tweet);
+
+        // only alice's live delivery counts
+        let req = Request::get("/19/views/5").body(Body::empty()).unwrap();
+        assert_eq!(send(router, req).await.1, "1");
+    }
+
+    #[tokio::test]
+    async fn day19_tweets_are_rate_limited() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let join = |user: &str| {
+            let url = format!("ws://{addr}/19/ws/room/6/user/{user}");
+            async move { tokio_tungstenite::connect_async(url).await.unwrap().0 }
+        };
+
+        let mut alice = join("alice").await;
+        let mut bob = join("bob").await;
+        wait_for_subscribers(&router, 6, 2).await;
+
+        let burst = TWEET_RATE_LIMIT as usize + 5;
+        for i in 0..burst {
+            let tweet = json!({"message": i.to_string()}).to_string();
+            alice.send(Message::Text(tweet)).await.unwrap();
+        }
+
+        // every tweet is answered, either by its echo or by an error
+        let mut limited = 0;
+        for _ in 0..burst {
+            if next_json(&mut alice).await == json!({"error": "rate_limited"}) {
+                limited += 1;
+            }
+        }
+        assert_eq!(limited, 5);
+
+        let req = Request::post("/19/room/6/user/curl/tweet")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({"message": "done"}).to_string()))
+            .unwrap();
+        assert_eq!(send(router, req).await.0, StatusCode::ACCEPTED);
+
+        let mut broadcast = 0;
+        while next_json(&mut bob).await["message"] != "done" {
+            broadcast += 1;
+        }
+        assert_eq!(broadcast, TWEET_RATE_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn day20_gzipped_tar_counts_the_same() {
+        let tar = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/cookiejar.tar")).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        for uri in ["/20/archive_files", "/20/archive_files_size"] {
+            let plain = send(
+                router(),
+                Request::post(uri).body(Body::from(tar.clone())).unwrap(),
+            );
+            let gzipped = send(
+                router(),
+                Request::post(uri).body(Body::from(gz.clone())).unwrap(),
+            );
+            let (plain, gzipped) = (plain.await, gzipped.await);
+            assert_eq!(plain.0, StatusCode::OK);
+            assert_eq!(plain, gzipped);
+        }
+    }
+
+    #[tokio::test]
+    async fn day20_cookie_custom_branch() {
+        let jar = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/cookiejar.tar")).unwrap();
+        let cookie = |query: &str| {
+            let req = Request::post(format!("/20/cookie?{query}"))
+                .body(Body::from(jar.clone()))
+                .unwrap();
+            send(router(), req)
+        };
+
+        assert_eq!(
+            cookie("branch=igloo&file=cold&needle=brrr").await,
+            (
+                StatusCode::OK,
+                "Santa e80b29cb02147dae049e9904a2d54ec5ab8493ca".into()
+            )
+        );
+        assert_eq!(
+            cookie("branch=igloo&file=../cold&needle=brrr").await.0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn day20_archive_files_list_keeps_order() {
+        let mut builder = tar::Builder::new(vec![]);
+        let mut dir = tar::Header::new_gnu();
+        dir.set_entry_type(tar::EntryType::Directory);
+        dir.set_size(0);
+        dir.set_cksum();
+        builder
+            .append_data(&mut dir, "dir/", std::io::empty())
+            .unwrap();
+        for (path, content) in [("dir/b.txt", "world!"), ("a.txt", "hello")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        let archive = builder.into_inner().unwrap();
+
+        let req = Request::post("/20/archive_files_list")
+            .body(Body::from(archive))
+            .unwrap();
+        let (status, body) = send(router(), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([
+                {"name": "dir/", "size": 0, "entry_type": "directory"},
+                {"name": "dir/b.txt", "size": 6, "entry_type": "regular"},
+                {"name": "a.txt", "size": 5, "entry_type": "regular"},
+            ])
+        );
+    }
+
+    #[test]
+    fn dms_seconds_carry_at_sixty() {
+        let dms = |d: f64, m: f64, s: f64| d + m / 60.0 + s / 3600.0;
+
+        assert_eq!(
+            format_dms(dms(12.0, 30.0, 59.9996), ['N', 'S']),
+            "12°31'0.000''N"
+        );
+        assert_eq!(
+            format_dms(dms(12.0, 59.0, 59.9996), ['N', 'S']),
+            "13°0'0.000''N"
+        );
+        assert_eq!(
+            format_dms(-dms(30.0, 59.0, 59.9999), ['E', 'W']),
+            "31°0'0.000''W"
+        );
+        // just below the rounding boundary nothing carries
+        assert_eq!(
+            format_dms(dms(12.0, 30.0, 59.9994), ['N', 'S']),
+            "12°30'59.999''N"
+        );
+    }
+
+    #[tokio::test]
+    async fn day21_country_full_name() {
+        // Kansas
+        let cell = "1000011110100011001001100101010000101100111101010011101001110001";
+
+        assert_eq!(
+            get(&format!("/21/country/{cell}")).await,
+            (StatusCode::OK, "United".into())
+        );
+        assert_eq!(
+            get(&format!("/21/country/{cell}?full=true")).await,
+            (StatusCode::OK, "United States of America".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn day21_country_outside_iso() {
+        // Kosovo
+        let cell = "0001001101010011011100000000000011100010000001110110100011101011";
+
+        for uri in ["/21/country", "/21/locate"] {
+            let (status, body) = get(&format!("{uri}/{cell}")).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"error": "unknown country", "status": 404, "country_code": "XK"})
+            );
+        }
+    }
+
+    #[test]
+    fn country_boundaries_are_parsed_once() {
+        assert!(Arc::ptr_eq(&country_boundaries(), &country_boundaries()));
+    }
+
+    #[tokio::test]
+    async fn day22_malformed_bodies() {
+        let post = |uri: &'static str, body: &'static str| {
+            send(router(), Request::post(uri).body(Body::from(body)).unwrap())
+        };
+
+        let (status, body) = post("/22/integers", "1\n2\nthree\n").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("line 3"), "{body}");
+
+        // two stars announced, only one given
+        let (status, body) = post("/22/rocket", "2\n0 0 0\n").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("line 3"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn day22_rocket_path() {
+        let rocket = |uri: &'static str, body: &'static str| {
+            send(router(), Request::post(uri).body(Body::from(body)).unwrap())
+        };
+        let body = "3\n0 0 0\n0 1 0\n0 1 1\n2\n0 1\n1 2\n";
+
+        assert_eq!(
+            rocket("/22/rocket", body).await,
+            (StatusCode::OK, "2 2.000".into())
+        );
+        let (status, path) = rocket("/22/rocket?path=true", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&path).unwrap(),
+            json!({"depth": 2, "distance": 2.0, "path": [0, 1, 2]})
+        );
+
+        // star 2 has no portal at all
+        let (status, body) = rocket("/22/rocket", "3\n0 0 0\n0 1 0\n0 1 1\n1\n0 1\n").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "error": "star 2 is unreachable from star 0",
+                "status": 422,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn health_degraded_without_database() {
+        let pool = lazy_pool();
+        pool.close().await;
+        let config = AppConfig::default();
+        let router = build_router(pool, TwitterState::new(&config), &config);
+
+        let (status, body) =
+            send(router, Request::get("/health").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"status": "degraded"})
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests() {
+        let router = router();
+        let scrape = || {
+            send(
+                router.clone(),
+                Request::get("/metrics").body(Body::empty()).unwrap(),
+            )
+        };
+        let hello = || {
+            send(
+                router.clone(),
+                Request::get("/").body(Body::empty()).unwrap(),
+            )
+        };
+        let line = r#"http_requests_total{method="GET",route="/",status="200"}"#;
+
+        hello().await;
+        let (status, body) = scrape().await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!("{line} 1\n")), "{body}");
+
+        hello().await;
+        let (_, body) = scrape().await;
+        assert!(body.contains(&format!("{line} 2\n")), "{body}");
+        assert!(body.contains(r#"http_request_duration_seconds_count{route="/"} 2"#));
+    }
+
+    #[tokio::test]
+    async fn app_error_statuses() {
+        let cases = [
+            (
+                AppError::bad_request(anyhow::anyhow!("x")),
+                StatusCode::BAD_REQUEST,
+            ),
+            (AppError::not_found("x", json!({})), StatusCode::NOT_FOUND),
+            (AppError::PayloadTooLarge, StatusCode::PAYLOAD_TOO_LARGE),
+            (
+                AppError::unsupported_media_type(anyhow::anyhow!("x")),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                AppError::unprocessable(anyhow::anyhow!("x")),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                AppError::upstream(anyhow::anyhow!("x")),
+                StatusCode::BAD_GATEWAY,
+            ),
+            // anything converted with `?` is an internal error
+            (
+                AppError::from(anyhow::anyhow!("x")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ];
+
+        for (err, status) in cases {
+            assert_eq!(err.status(), status);
+            let resp = err.into_response();
+            assert_eq!(resp.status(), status);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["status"], status.as_u16());
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_carry_a_request_id() {
+        let hello = || router().oneshot(Request::get("/").body(Body::empty()).unwrap());
+
+        let a = hello().await.unwrap();
+        let b = hello().await.unwrap();
+        let id = |resp: &Response| resp.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&id(&a)).is_ok());
+        assert_ne!(id(&a), id(&b));
+
+        // an id from the caller is kept
+        let req = Request::get("/")
+            .header("x-request-id", "abc")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router().oneshot(req).await.unwrap();
+        assert_eq!(resp.headers()["x-request-id"], "abc");
+    }
+
+    #[tokio::test]
+    async fn day6_body_limit() {
+        let req = Request::post("/6")
+            .body(Body::from(vec![b'a'; 9 * 1024 * 1024]))
+            .unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_rejected() {
+        let body = || Body::from(vec![b'a'; 9 * 1024 * 1024]);
+
+        let req = Request::post("/4/strength")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body())
+            .unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // uploads have a larger limit of their own
+        let req = Request::post("/20/archive_files").body(body()).unwrap();
+        assert_ne!(send(router(), req).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let config = AppConfig {
+            max_upload_bytes: 1024,
+            ..Default::default()
+        };
+        let req = Request::post("/20/archive_files").body(body()).unwrap();
+        assert_eq!(
+            send(router_with(&config), req).await.0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[tokio::test]
+    async fn day19_shutdown_closes_sockets() {
+        use tokio_tungstenite::tungstenite::{self, protocol::frame::coding::CloseCode, Message};
+
+        let config = AppConfig::default();
+        let twitter = TwitterState::new(&config);
+        let router = build_router(lazy_pool(), twitter.clone(), &config);
+        let addr = serve(router.clone()).await;
+
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/room/7/user/elf"))
+                .await
+                .unwrap();
+        wait_for_subscribers(&router, 7, 1).await;
+
+        twitter.shutdown.cancel();
+        let Message::Close(Some(frame)) = ws.next().await.unwrap().unwrap() else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(frame.code, CloseCode::Away);
+
+        // and nobody new gets in
+        let err = tokio_tungstenite::connect_async(format!("ws://{addr}/19/ws/room/7/user/late"))
+            .await
+            .unwrap_err();
+        let tungstenite::Error::Http(resp) = err else {
+            panic!("expected the upgrade to be refused, got {err}");
+        };
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_total_gzip() {
+        let router = router();
+        let seed = json!({
+            "regions": [{"id": 1, "name": "North Pole"}, {"id": 2, "name": "South Pole"}],
+            "orders": [
+                {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 10},
+                {"id": 2, "region_id": 2, "gift_name": "Sock", "quantity": 2},
+            ],
+        });
+        reset_and_seed(&router, seed).await;
+
+        let total = |encoding: Option<&str>| {
+            let mut req = Request::get("/18/regions/total");
+            if let Some(encoding) = encoding {
+                req = req.header(header::ACCEPT_ENCODING, encoding);
+            }
+            router.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        let plain = total(None).await.unwrap();
+        assert!(!plain.headers().contains_key(header::CONTENT_ENCODING));
+        let plain = axum::body::to_bytes(plain.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let gzipped = total(Some("gzip")).await.unwrap();
+        assert_eq!(gzipped.headers()[header::CONTENT_ENCODING], "gzip");
+        let gzipped = axum::body::to_bytes(gzipped.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut decoded = vec![];
+        flate2::read::GzDecoder::new(&gzipped[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
+
+    #[tokio::test]
+    async fn ws_upgrade_ignores_accept_encoding() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest as _;
+
+        let addr = serve(router()).await;
+        let mut req = format!("ws://{addr}/19/ws/echo")
+            .into_client_request()
+            .unwrap();
+        req.headers_mut()
+            .insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
+
+        let (_, resp) = tokio_tungstenite::connect_async(req).await.unwrap();
+        assert!(!resp.headers().contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[tokio::test]
+    async fn day14_escaping_and_title() {
+        let content = "<script>alert(1)</script>";
+
+        let (status, unsafe_page) = post_json("/14/unsafe", json!({ "content": content })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(unsafe_page.contains(content));
+        assert!(unsafe_page.contains("<title>CCH23 Day 14</title>"));
+
+        let (_, safe_page) = post_json("/14/safe", json!({ "content": content })).await;
+        assert!(!safe_page.contains(content));
+        assert!(safe_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+
+        // the title is escaped either way
+        let (_, page) = post_json(
+            "/14/unsafe",
+            json!({"content": "hi", "title": "Elves & <Co>"}),
+        )
+        .await;
+        assert!(page.contains("<title>Elves &amp; &lt;Co&gt;</title>"));
+    }
+
+    #[tokio::test]
+    async fn configure_pool_times_out_on_unreachable_database() {
+        // a blackholed address never answers, so only the acquire timeout
+        // stops startup from hanging
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://postgres@10.255.255.1:5432/cch23")
+            .unwrap();
+        let config = AppConfig {
+            db_acquire_timeout: Duration::from_millis(200),
+            ..AppConfig::default()
+        };
+
+        let result = tokio::time::timeout(Duration::from_secs(5), configure_pool(pool, &config))
+            .await
+            .expect("startup hung past the acquire timeout");
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_18_concurrent_resets() {
+        let router = router();
+        reset_and_seed(
+            &router,
+            json!({
+                "regions": [{"id": 1, "name": "North"}],
+                "orders": [{"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 3}],
+            }),
+        )
+        .await;
+
+        let resets = (0..4).map(|_| {
+            let req = Request::post("/18/reset").body(Body::empty()).unwrap();
+            send(router.clone(), req)
+        });
+        for (status, body) in futures::future::join_all(resets).await {
+            assert_eq!(status, StatusCode::OK, "{body}");
+        }
+
+        // every table is back and empty
+        let (_, body) = get("/13/orders/total").await;
+        assert_eq!(body, json!({"total": 0}).to_string());
+        let req = Request::get("/18/regions/total")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send(router, req).await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day13_list_orders_filters_and_pages() {
+        let router = router();
+        reset_and_seed(
+            &router,
+            json!({
+                "orders": [
+                    {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 1},
+                    {"id": 2, "region_id": 2, "gift_name": "Sled", "quantity": 2},
+                    {"id": 3, "region_id": 1, "gift_name": "Doll", "quantity": 3},
+                    {"id": 4, "region_id": 1, "gift_name": "Sled", "quantity": 4},
+                ],
+            }),
+        )
+        .await;
+
+        let ids = |uri: &'static str| {
+            let router = router.clone();
+            async move {
+                let req = Request::get(uri).body(Body::empty()).unwrap();
+                let (status, body) = send(router, req).await;
+                assert_eq!(status, StatusCode::OK, "{body}");
+                serde_json::from_str::<Vec<serde_json::Value>>(&body)
+                    .unwrap()
+                    .iter()
+                    .map(|order| order["id"].as_i64().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(ids("/13/orders").await, [1, 2, 3, 4]);
+        assert_eq!(ids("/13/orders?region_id=1").await, [1, 3, 4]);
+        assert_eq!(ids("/13/orders?region_id=1&gift_name=Sled").await, [1, 4]);
+        assert_eq!(ids("/13/orders?limit=2&offset=1").await, [2, 3]);
+        assert_eq!(ids("/13/orders?gift_name=Sled&offset=2").await, [4]);
+        // bound, not spliced into the SQL
+        assert!(ids("/13/orders?gift_name=x'%20OR%20'1'='1")
+            .await
+            .is_empty());
+    }
+
+    // a store that pretends every saved timer started 42 seconds ago
+    #[derive(Default)]
+    struct FakeTimerStore {
+        saved: Mutex<Vec<String>>,
+    }
+
+    impl TimerStore for FakeTimerStore {
+        fn save(&self, key: &str) {
+            self.saved.lock().unwrap().push(key.to_string());
+        }
+
+        fn load(&self, key: &str) -> Option<i64> {
+            let saved = self.saved.lock().unwrap();
+            saved.iter().any(|saved| saved == key).then_some(42)
+        }
+
+        fn remove(&self, key: &str) -> bool {
+            let mut saved = self.saved.lock().unwrap();
+            let len = saved.len();
+            saved.retain(|saved| saved != key);
+            saved.len() != len
+        }
+    }
+
+    #[tokio::test]
+    async fn day12_handlers_use_the_timer_store() {
+        let store = Arc::new(FakeTimerStore::default());
+        let timers = || State(store.clone() as Arc<dyn TimerStore>);
+
+        assert!(day12_task1_post(timers(), Path("packet".to_string()))
+            .await
+            .is_ok());
+        assert_eq!(*store.saved.lock().unwrap(), ["packet"]);
+
+        let loaded = day12_task1_get(timers(), Path("packet".to_string())).await;
+        assert!(matches!(loaded.as_deref(), Ok("42")));
+
+        assert_eq!(
+            day12_delete(timers(), Path("packet".to_string())).await,
+            StatusCode::NO_CONTENT
+        );
+        let loaded = day12_task1_get(timers(), Path("packet".to_string())).await;
+        assert!(matches!(loaded, Err(AppError::NotFound(..))));
+    }
+
+    #[tokio::test]
+    async fn cors_preflight() {
+        let preflight = |router: Router, origin: &'static str| async move {
+            let req = Request::options("/4/strength")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+                .body(Body::empty())
+                .unwrap();
+            let resp = router.oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            preflight(router(), "https://elf.example").await.as_deref(),
+            Some("*")
+        );
+
+        let config = AppConfig {
+            production: true,
+            cors_origins: Some(vec!["https://north.pole".to_string()]),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            preflight(router_with(&config), "https://north.pole")
+                .await
+                .as_deref(),
+            Some("https://north.pole")
+        );
+        assert_eq!(
+            preflight(router_with(&config), "https://elf.example").await,
+            None
+        );
+
+        // production without a list lets nobody in
+        let config = AppConfig {
+            production: true,
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            preflight(router_with(&config), "https://elf.example").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn day19_long_tweets_get_an_error_frame() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let join = |user: &str| {
+            let url = format!("ws://{addr}/19/ws/room/7/user/{user}");
+            async move { tokio_tungstenite::connect_async(url).await.unwrap().0 }
+        };
+
+        let mut alice = join("alice").await;
+        let mut bob = join("bob").await;
+        wait_for_subscribers(&router, 7, 2).await;
+
+        // 128 graphemes but far more bytes still fits
+        let fits = "🎄".repeat(128);
+        alice
+            .send(Message::Text(json!({ "message": fits }).to_string()))
+            .await
+            .unwrap();
+        let tweet = json!({"user": "alice", "message": fits});
+        assert_eq!(next_json(&mut alice).await, tweet);
+        assert_eq!(next_json(&mut bob).await, tweet);
+
+        alice
+            .send(Message::Text(
+                json!({ "message": "🎄".repeat(129) }).to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_json(&mut alice).await,
+            json!({"error": "message_too_long", "max": 128})
+        );
+
+        // bob never saw the rejected tweet: the next thing he gets is this one
+        alice
+            .send(Message::Text(json!({"message": "short"}).to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_json(&mut bob).await,
+            json!({"user": "alice", "message": "short"})
+        );
+    }
+
+    #[tokio::test]
+    async fn day19_room_tokens() {
+        use tokio_tungstenite::tungstenite::Error;
+
+        let config = AppConfig {
+            room_tokens: HashMap::from([(1, "secret".to_string())]),
+            ..AppConfig::default()
+        };
+        let addr = serve(router_with(&config)).await;
+        let connect = |path: &str| {
+            let url = format!("ws://{addr}/19/ws/room/{path}");
+            async move { tokio_tungstenite::connect_async(url).await }
+        };
+
+        assert!(connect("1/user/alice?token=secret").await.is_ok());
+        for path in ["1/user/alice?token=guess", "1/user/alice"] {
+            match connect(path).await {
+                Err(Error::Http(resp)) => assert_eq!(resp.status(), StatusCode::UNAUTHORIZED),
+                other => panic!("{path}: expected a 401, got {other:?}"),
+            }
+        }
+
+        // rooms without a secret stay open
+        assert!(connect("2/user/alice").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn day19_ping_survives_binary_and_control_frames() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let addr = serve(router()).await;
+        let url = format!("ws://{addr}/19/ws/ping");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        ws.send(Message::Binary(vec![1, 2, 3])).await.unwrap();
+        ws.send(Message::Ping(vec![])).await.unwrap();
+        ws.send(Message::Text("serve".into())).await.unwrap();
+        ws.send(Message::Text("ping".into())).await.unwrap();
+
+        // the server answers our ping before it gets to the text frames
+        assert!(matches!(ws.next().await, Some(Ok(Message::Pong(_)))));
+        assert!(matches!(ws.next().await, Some(Ok(Message::Text(msg))) if msg == "pong"));
+
+        // a close frame ends the session cleanly
+        ws.close(None).await.unwrap();
+        assert!(matches!(
+            ws.next().await,
+            Some(Ok(Message::Close(_))) | None
+        ));
+    }
+
+    #[tokio::test]
+    async fn day19_stats_counts() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let router = router();
+        let addr = serve(router.clone()).await;
+        let join = |room: usize, user: &str| {
+            let url = format!("ws://{addr}/19/ws/room/{room}/user/{user}");
+            async move { tokio_tungstenite::connect_async(url).await.unwrap().0 }
+        };
+
+        let mut alice = join(1, "alice").await;
+        let mut bob = join(1, "bob").await;
+        let mut carol = join(2, "carol").await;
+        wait_for_subscribers(&router, 1, 2).await;
+        wait_for_subscribers(&router, 2, 1).await;
+
+        alice
+            .send(Message::Text(json!({"message": "one"}).to_string()))
+            .await
+            .unwrap();
+        carol
+            .send(Message::Text(json!({"message": "two"}).to_string()))
+            .await
+            .unwrap();
+        for ws in [&mut alice, &mut bob, &mut carol] {
+            next_json(ws).await;
+        }
+
+        let req = Request::get("/19/stats").body(Body::empty()).unwrap();
+        let (status, body) = send(router, req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"views": 3, "rooms": 2, "total_subscribers": 3})
+        );
+    }
+
+    #[test]
+    fn representation_from_accept() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, header::HeaderValue::from_static(value));
+            Representation::from_headers(&headers)
+        };
+
+        assert!(matches!(accept("text/csv"), Representation::Csv));
+        assert!(matches!(
+            accept("application/json;q=0.9, text/csv; charset=utf-8"),
+            Representation::Csv
+        ));
+        assert!(matches!(accept("application/json"), Representation::Json));
+        assert!(matches!(accept("*/*"), Representation::Json));
+        assert!(matches!(
+            Representation::from_headers(&HeaderMap::new()),
+            Representation::Json
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn day18_total_json_and_csv() {
+        let router = router();
+        let seed = json!({
+            "regions": [{"id": 1, "name": "Oslo, Norway"}, {"id": 2, "name": "Ankara"}],
+            "orders": [
+                {"id": 1, "region_id": 1, "gift_name": "Sled", "quantity": 10},
+                {"id": 2, "region_id": 2, "gift_name": "Sock", "quantity": 2},
+            ],
+        });
+        reset_and_seed(&router, seed).await;
+
+        let total = |accept: &'static str| {
+            let req = Request::get("/18/regions/total")
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap();
+            send(router.clone(), req)
+        };
+
+        let (_, body) = total("application/json").await;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([
+                {"region": "Ankara", "total": 2},
+                {"region": "Oslo, Norway", "total": 10},
+            ])
+        );
+
+        let (status, body) = total("text/csv").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "region,total\nAnkara,2\n\"Oslo, Norway\",10\n");
+    }
+
+    #[tokio::test]
+    async fn day7_bake_json_matches_cookie() {
+        let payload = json!({
+            "recipe": {"flour": 95, "sugar": 50, "butter": 30, "baking powder": 10, "chocolate chips": 50},
+            "pantry": {"flour": 385, "sugar": 507, "butter": 2122, "baking powder": 865, "chocolate chips": 457},
+        });
+
+        let cookie = base64::prelude::BASE64_STANDARD.encode(payload.to_string());
+        let req = Request::get("/7/bake")
+            .header(header::COOKIE, format!("recipe={cookie}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, from_cookie) = send(router(), req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, from_json) = post_json("/7/bake-json", payload).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(from_json, from_cookie);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&from_json).unwrap()["cookies"],
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn day7_bake_output_is_stable() {
+        let pantry = ('a'..='z')
+            .rev()
+            .map(|c| (c.to_string(), json!(100)))
+            .collect::<serde_json::Map<_, _>>();
+        let payload = json!({"recipe": {"m": 7, "c": 3}, "pantry": pantry});
+
+        let (_, first) = post_json("/7/bake-json", payload.clone()).await;
+        let (_, second) = post_json("/7/bake-json", payload).await;
+        assert_eq!(first, second);
+        assert!(first.starts_with(r#"{"cookies":14,"pantry":{"a":100,"b":100,"c":58,"d":100,"#));
+    }
+
+    #[tokio::test]
+    async fn day8_drop_height_and_gravity() {
+        let pikachu = || async { Json(json!({"name": "pikachu", "weight": 60})) };
+        let mock = serve(Router::new().route("/pokemon/25/", axum::routing::get(pikachu))).await;
+        let router = router_with(&AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        });
+        let drop = |query: &str| {
+            let req = Request::get(format!("/8/drop/25{query}"))
+                .body(Body::empty())
+                .unwrap();
+            send(router.clone(), req)
+        };
+
+        // the defaults reproduce the original 10m drop at 9.825 m/s²
+        assert_eq!(drop("").await, (StatusCode::OK, "84.107074613257".into()));
+        assert_eq!(
+            drop("?height=20&gravity=9.81").await,
+            (
+                StatusCode::OK,
+                format!("{:.12}", 6.0 * (2.0f64 * 9.81 * 20.0).sqrt())
+            )
+        );
+
+        for query in ["?height=-1", "?gravity=0", "?gravity=NaN", "?height=inf"] {
+            assert_eq!(drop(query).await.0, StatusCode::BAD_REQUEST, "{query}");
+        }
+    }
+
+    #[tokio::test]
+    async fn day8_weights_batch() {
+        let weights = HashMap::from([(1, 69), (25, 60)]);
+        let pokemon = move |Path(id): Path<u64>| async move {
+            match weights.get(&id) {
+                Some(weight) => Json(json!({ "weight": weight })).into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        };
+        let mock = serve(Router::new().route("/pokemon/:id/", axum::routing::get(pokemon))).await;
+        let config = AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        };
+
+        let req = Request::post("/8/weights")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("[1, 25, 9999]"))
+            .unwrap();
+        let (status, body) = send(router_with(&config), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"1": 6.9, "25": 6.0, "9999": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn day4_contest_rejects_nan_speed() {
+        // JSON itself has no NaN, so hand the handler one directly
+        let reindeer = Reindeer {
+            name: "Dasher".into(),
+            strength: 5,
+            speed: f64::NAN,
+            height: 100,
+            antler_width: 10,
+            snow_magic_power: 1,
+            favorite_food: "hay".into(),
+            candies: 2,
+        };
+        let resp = day4_task2(Query(ContestQuery { by: None }), AppJson(vec![reindeer])).await;
+        let Err(err) = resp else {
+            panic!("NaN speed was accepted");
+        };
+        let resp = err.into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            json!({"error": "invalid reindeer stats", "status": 400})
+        );
+
+        // an overflowing speed never gets past the JSON parser
+        let req = Request::post("/4/contest")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"[{"name": "Dasher", "strength": 5, "speed": 1e999}]"#,
+            ))
+            .unwrap();
+        assert_eq!(send(router(), req).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn openapi_document() {
+        let (status, body) = get("/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+        let spec: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        let contest = &spec["paths"]["/4/contest"]["post"];
+        assert_eq!(
+            contest.pointer("/requestBody/content/application~1json/schema/items/$ref"),
+            Some(&json!("#/components/schemas/Reindeer"))
+        );
+        assert_eq!(contest["parameters"][0]["name"], "by");
+
+        // every reference points at a schema that exists
+        fn refs<'a>(value: &'a serde_json::Value, out: &mut Vec<&'a str>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    if let Some(target) = map.get("$ref").and_then(|r| r.as_str()) {
+                        out.push(target);
+                    }
+                    map.values().for_each(|v| refs(v, out));
+                }
+                serde_json::Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+                _ => {}
+            }
+        }
+        let mut targets = vec![];
+        refs(&spec, &mut targets);
+        assert!(!targets.is_empty());
+        for target in targets {
+            let name = target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "dangling {target}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn day8_retries_server_errors() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flaky = {
+            let calls = calls.clone();
+            move |Path(id): Path<u64>| async move {
+                // 25 fails twice, then recovers; everything else is unknown
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    _ if id != 25 => StatusCode::NOT_FOUND.into_response(),
+                    0 | 1 => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                    _ => Json(json!({"weight": 60})).into_response(),
+                }
+            }
+        };
+        let mock = serve(Router::new().route("/pokemon/:id/", axum::routing::get(flaky))).await;
+        let config = AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        };
+
+        let req = Request::get("/8/weight/25").body(Body::empty()).unwrap();
+        assert_eq!(
+            send(router_with(&config), req).await,
+            (StatusCode::OK, "6".to_string())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // a 404 is final; it is not retried
+        let req = Request::get("/8/weight/1").body(Body::empty()).unwrap();
+        assert_eq!(
+            send(router_with(&config), req).await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // an upstream that never recovers is given up on
+        let down = serve(Router::new().route(
+            "/pokemon/25/",
+            axum::routing::get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        ))
+        .await;
+        let config = AppConfig {
+            pokeapi_url: format!("http://{down}"),
+            ..Default::default()
+        };
+        let req = Request::get("/8/weight/25").body(Body::empty()).unwrap();
+        assert_eq!(
+            send(router_with(&config), req).await.0,
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[tokio::test]
+    async fn day8_cached_miss_skips_upstream() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let missing = {
+            let calls = calls.clone();
+            move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                StatusCode::NOT_FOUND
+            }
+        };
+        let mock = serve(Router::new().route("/pokemon/:id/", axum::routing::get(missing))).await;
+        let router = router_with(&AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            ..Default::default()
+        });
+
+        for uri in ["/8/weight/9999", "/8/drop/9999", "/8/weight/9999"] {
+            let req = Request::get(uri).body(Body::empty()).unwrap();
+            assert_eq!(send(router.clone(), req).await.0, StatusCode::NOT_FOUND);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // once the miss expires, the next lookup goes upstream again
+        let router = router_with(&AppConfig {
+            pokeapi_url: format!("http://{mock}"),
+            poke_miss_ttl: Duration::ZERO,
+            ..Default::default()
+        });
+        for _ in 0..2 {
+            let req = Request::get("/8/weight/9999").body(Body::empty()).unwrap();
+            assert_eq!(send(router.clone(), req).await.0, StatusCode::NOT_FOUND);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    fn png_upload(uri: &str, image: image::DynamicImage) -> Request<Body> {
+        let mut png = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let mut body = b"--X\r\n\
+            Content-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\n\
+            Content-Type: image/png\r\n\r\n"
+            .to_vec();
+        body.extend(png);
+        body.extend(b"\r\n--X--\r\n");
+
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn day11_rgba_and_grayscale() {
+        let rgba = image::RgbaImage::from_fn(3, 1, |x, _| match x {
+            0 => image::Rgba([255, 0, 0, 255]),
+            // red, but fully transparent
+            1 => image::Rgba([255, 0, 0, 0]),
+            _ => image::Rgba([0, 255, 0, 255]),
+        });
+        let req = png_upload("/11/red_pixels", rgba.into());
+        assert_eq!(send(router(), req).await, (StatusCode::OK, "1".into()));
+
+        let gray = image::GrayImage::from_pixel(4, 4, image::Luma([200]));
+        let req = png_upload("/11/red_pixels", gray.into());
+        assert_eq!(send(router(), req).await, (StatusCode::OK, "0".into()));
+    }
+
+    #[tokio::test]
+    async fn day11_red_bbox() {
+        let square = image::RgbImage::from_fn(10, 10, |x, y| {
+            if (2..5).contains(&x) && (3..6).contains(&y) {
+                image::Rgb([200, 10, 10])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+        let (status, body) = send(router(), png_upload("/11/red_bbox", square.into())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"min_x": 2, "min_y": 3, "max_x": 4, "max_y": 5, "count": 9})
+        );
+
+        let blank = image::RgbImage::from_pixel(10, 10, image::Rgb([255, 255, 255]));
+        let (_, body) = send(router(), png_upload("/11/red_bbox", blank.into())).await;
+        assert_eq!(body, "null");
+    }
+
+    #[tokio::test]
+    async fn day14_head_content_length() {
+        let content_length = |req: Request<Body>| async move {
+            let resp = router().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let length = resp.headers()[header::CONTENT_LENGTH]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (length.parse::<usize>().unwrap(), body.len())
+        };
+        let request = |method: Method, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        for uri in ["/14/unsafe", "/14/safe"] {
+            let page = json!({"content": "<b>ho ho ho</b>", "title": "Elves & Co"});
+            let (length, body) = content_length(request(Method::POST, uri, page.clone())).await;
+            assert_eq!(length, body);
+
+            let (head_length, head_body) = content_length(request(Method::HEAD, uri, page)).await;
+            assert_eq!((head_length, head_body), (length, 0));
+
+            // without a body, HEAD describes the page for empty content
+            let (empty, _) =
+                content_length(request(Method::POST, uri, json!({"content": ""}))).await;
+            let req = Request::head(uri).body(Body::empty()).unwrap();
+            assert_eq!(content_length(req).await, (empty, 0));
+        }
+    }
 }
@@ -0,0 +1,498 @@
+use axum::Json;
+use serde_json::{json, Value};
+
+fn string_array() -> Value {
+    json!({ "type": "array", "items": { "type": "string" } })
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({ "required": true, "content": { "application/json": { "schema": schema } } })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+fn empty_response(description: &str) -> Value {
+    json!({ "description": description })
+}
+
+fn path_param(name: &str, ty: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": { "type": ty } })
+}
+
+fn query_param(name: &str, ty: &str) -> Value {
+    json!({ "name": name, "in": "query", "required": false, "schema": { "type": ty } })
+}
+
+fn components() -> Value {
+    json!({
+        "schemas": {
+            "Reindeer": {
+                "type": "object",
+                "required": ["name", "strength"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "strength": { "type": "integer" },
+                    "speed": { "type": "number" },
+                    "height": { "type": "integer" },
+                    "antler_width": { "type": "integer" },
+                    "snow_magic_power": { "type": "integer" },
+                    "favorite_food": { "type": "string" },
+                    "cAnD13s_3ATeN-yesT3rdAy": { "type": "integer" },
+                },
+            },
+            "Contest": {
+                "type": "object",
+                "properties": {
+                    "fastest": { "type": "string" },
+                    "tallest": { "type": "string" },
+                    "magician": { "type": "string" },
+                    "consumer": { "type": "string" },
+                },
+            },
+            "Winner": {
+                "type": "object",
+                "properties": {
+                    "winner": { "type": "string" },
+                    "value": { "type": "number" },
+                },
+            },
+            "Order": {
+                "type": "object",
+                "required": ["id", "region_id", "gift_name", "quantity"],
+                "properties": {
+                    "id": { "type": "integer" },
+                    "region_id": { "type": "integer" },
+                    "gift_name": { "type": "string" },
+                    "quantity": { "type": "integer" },
+                },
+            },
+            "Region": {
+                "type": "object",
+                "required": ["id", "name"],
+                "properties": {
+                    "id": { "type": "integer" },
+                    "name": { "type": "string" },
+                },
+            },
+            "PasswordInput": {
+                "type": "object",
+                "required": ["input"],
+                "properties": { "input": { "type": "string" } },
+            },
+            "PasswordResult": {
+                "type": "object",
+                "properties": {
+                    "result": { "type": "string", "enum": ["nice", "naughty"] },
+                    "reason": { "type": "string" },
+                },
+            },
+        },
+    })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn array_of(name: &str) -> Value {
+    json!({ "type": "array", "items": schema_ref(name) })
+}
+
+fn paths() -> Value {
+    json!({
+        "/4/strength": {
+            "post": {
+                "requestBody": json_body(array_of("Reindeer")),
+                "responses": { "200": empty_response("Sum of strengths as plain text") },
+            },
+        },
+        "/4/contest": {
+            "post": {
+                "parameters": [{
+                    "name": "by",
+                    "in": "query",
+                    "required": false,
+                    "schema": {
+                        "type": "string",
+                        "enum": ["speed", "height", "snow_magic_power", "candies", "strength"],
+                    },
+                }],
+                "requestBody": json_body(array_of("Reindeer")),
+                "responses": {
+                    "200": json_response(
+                        "Contest winners, or the single winner for `by`",
+                        json!({ "oneOf": [schema_ref("Contest"), schema_ref("Winner")] }),
+                    ),
+                    "400": empty_response("No reindeer, a non-finite speed, or an unknown `by`"),
+                },
+            },
+        },
+        "/5": {
+            "post": {
+                "parameters": [
+                    query_param("offset", "integer"),
+                    query_param("limit", "integer"),
+                    query_param("split", "integer"),
+                    {
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string", "enum": ["json", "ndjson"], "default": "json" },
+                    },
+                ],
+                "requestBody": json_body(string_array()),
+                "responses": {
+                    "200": {
+                        "description": "The selected names, chunked when `split` is given",
+                        "content": {
+                            "application/json": {
+                                "schema": { "oneOf": [string_array(), { "type": "array", "items": string_array() }] },
+                            },
+                            // one name, or one chunk, per line
+                            "application/x-ndjson": {
+                                "schema": { "oneOf": [{ "type": "string" }, string_array()] },
+                            },
+                        },
+                    },
+                    "400": empty_response("`split` is 0"),
+                },
+            },
+        },
+        "/5/explain": {
+            "get": {
+                "parameters": [
+                    query_param("offset", "integer"),
+                    query_param("limit", "integer"),
+                    query_param("split", "integer"),
+                    { "name": "len", "in": "query", "required": true, "schema": { "type": "integer" } },
+                ],
+                "responses": {
+                    "200": json_response("The window `/5` would select from a list of `len` names", json!({
+                        "type": "object",
+                        "properties": {
+                            "offset": { "type": "integer", "nullable": true },
+                            "limit": { "type": "integer", "nullable": true },
+                            "split": { "type": "integer", "nullable": true },
+                            "effective_start": { "type": "integer" },
+                            "effective_end": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+        },
+        "/12/save/{key}": {
+            "post": {
+                "parameters": [path_param("key", "string")],
+                "responses": {
+                    "200": json_response("Timer started", json!({
+                        "type": "object",
+                        "properties": {
+                            "key": { "type": "string" },
+                            "saved_at": { "type": "string", "format": "date-time" },
+                        },
+                    })),
+                },
+            },
+            "delete": {
+                "parameters": [path_param("key", "string")],
+                "responses": {
+                    "204": empty_response("Timer removed"),
+                    "404": empty_response("No such timer"),
+                },
+            },
+        },
+        "/12/load/{key}": {
+            "get": {
+                "parameters": [path_param("key", "string")],
+                "responses": {
+                    "200": empty_response("Elapsed seconds as plain text"),
+                    "404": empty_response("No such timer"),
+                },
+            },
+        },
+        "/12/ulids": {
+            "post": {
+                "requestBody": json_body(string_array()),
+                "responses": { "200": json_response("UUIDs in reverse order", string_array()) },
+            },
+        },
+        "/12/uuids": {
+            "post": {
+                "requestBody": json_body(string_array()),
+                "responses": { "200": json_response("ULIDs in reverse order", string_array()) },
+            },
+        },
+        "/12/ulid/{ulid}": {
+            "get": {
+                "parameters": [path_param("ulid", "string")],
+                "responses": {
+                    "200": json_response("What the ULID encodes", json!({
+                        "type": "object",
+                        "properties": {
+                            "uuid": { "type": "string", "format": "uuid" },
+                            "timestamp_ms": { "type": "integer" },
+                            "datetime_rfc3339": { "type": "string", "format": "date-time" },
+                            "weekday": { "type": "integer", "minimum": 0, "maximum": 6 },
+                            "is_christmas_eve": { "type": "boolean" },
+                            "lsb": { "type": "boolean" },
+                        },
+                    })),
+                    "400": empty_response("Not a valid ULID"),
+                },
+            },
+        },
+        "/12/ulids/{weekday}": {
+            "post": {
+                "parameters": [
+                    {
+                        "name": "weekday",
+                        "in": "path",
+                        "required": true,
+                        "description": "0 (Monday) to 6 (Sunday), or the day's name in any case",
+                        "schema": {
+                            "oneOf": [
+                                { "type": "integer", "minimum": 0, "maximum": 6 },
+                                {
+                                    "type": "string",
+                                    "enum": [
+                                        "monday", "tuesday", "wednesday", "thursday",
+                                        "friday", "saturday", "sunday",
+                                    ],
+                                },
+                            ],
+                        },
+                    },
+                    query_param("tz", "string"),
+                ],
+                "requestBody": json_body(string_array()),
+                "responses": {
+                    "200": json_response("ULID date statistics", json!({
+                        "type": "object",
+                        "properties": {
+                            "christmas eve": { "type": "integer" },
+                            "weekday": { "type": "integer" },
+                            "in the future": { "type": "integer" },
+                            "LSB is 1": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+        },
+        "/13/orders": {
+            "get": {
+                "parameters": [
+                    query_param("region_id", "integer"),
+                    query_param("gift_name", "string"),
+                    query_param("limit", "integer"),
+                    query_param("offset", "integer"),
+                ],
+                "responses": { "200": json_response("Matching orders", array_of("Order")) },
+            },
+            "post": {
+                "requestBody": json_body(array_of("Order")),
+                "responses": {
+                    "200": json_response("Number of new orders; known ids are skipped", json!({
+                        "type": "object",
+                        "properties": { "inserted": { "type": "integer" } },
+                    })),
+                },
+            },
+            "delete": {
+                "responses": { "204": empty_response("All orders removed") },
+            },
+        },
+        "/13/orders/total": {
+            "get": {
+                "responses": {
+                    "200": json_response("Total quantity, 0 when there are no orders", json!({
+                        "type": "object",
+                        "properties": { "total": { "type": "integer" } },
+                    })),
+                },
+            },
+        },
+        "/13/orders/region/{region_id}/total": {
+            "get": {
+                "parameters": [path_param("region_id", "integer")],
+                "responses": {
+                    "200": json_response("Total quantity ordered for one region", json!({
+                        "type": "object",
+                        "properties": {
+                            "region_id": { "type": "integer" },
+                            "total": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+        },
+        "/13/orders/popular": {
+            "get": {
+                "responses": {
+                    "200": json_response("Most popular gift", json!({
+                        "type": "object",
+                        "properties": { "popular": { "type": "string", "nullable": true } },
+                    })),
+                },
+            },
+        },
+        "/13/orders/popular/by_quantity": {
+            "get": {
+                "responses": {
+                    "200": json_response("Gift with the highest total quantity, or all tied ones", json!({
+                        "type": "object",
+                        "properties": {
+                            "popular": {
+                                "nullable": true,
+                                "oneOf": [{ "type": "string" }, string_array()],
+                            },
+                        },
+                    })),
+                },
+            },
+        },
+        "/18/orders": {
+            "post": {
+                "requestBody": json_body(array_of("Order")),
+                "responses": {
+                    "200": json_response("Number of new orders; known ids are skipped", json!({
+                        "type": "object",
+                        "properties": { "inserted": { "type": "integer" } },
+                    })),
+                },
+            },
+        },
+        "/18/regions": {
+            "post": {
+                "requestBody": json_body(array_of("Region")),
+                "responses": { "200": empty_response("Regions inserted") },
+            },
+            "delete": {
+                "responses": { "204": empty_response("All regions and their orders removed") },
+            },
+        },
+        "/18/seed": {
+            "post": {
+                "requestBody": json_body(json!({
+                    "type": "object",
+                    "properties": {
+                        "regions": array_of("Region"),
+                        "orders": array_of("Order"),
+                    },
+                })),
+                "responses": { "200": empty_response("Regions and orders inserted together") },
+            },
+        },
+        "/18/regions/total": {
+            "get": {
+                "parameters": [query_param("min_total", "integer")],
+                "responses": {
+                    "200": json_response("Total quantity per region", json!({
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "region": { "type": "string" },
+                                "total": { "type": "integer" },
+                            },
+                        },
+                    })),
+                },
+            },
+        },
+        "/18/regions/top_list/{limit}": {
+            "get": {
+                "parameters": [
+                    path_param("limit", "integer"),
+                    query_param("region_offset", "integer"),
+                    query_param("region_limit", "integer"),
+                ],
+                "responses": {
+                    "200": json_response("Top gifts per region", json!({
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "region": { "type": "string" },
+                                "top_gifts": string_array(),
+                            },
+                        },
+                    })),
+                },
+            },
+        },
+        "/15/nice": {
+            "post": {
+                "requestBody": json_body(schema_ref("PasswordInput")),
+                "responses": {
+                    "200": json_response("Nice password", schema_ref("PasswordResult")),
+                    "400": json_response("Naughty password", schema_ref("PasswordResult")),
+                },
+            },
+        },
+        "/15/game": {
+            "post": {
+                "parameters": [query_param("verbose", "boolean")],
+                "requestBody": json_body(schema_ref("PasswordInput")),
+                "responses": {
+                    "200": json_response("Nice password", schema_ref("PasswordResult")),
+                    "400": json_response("Naughty password", schema_ref("PasswordResult")),
+                },
+            },
+        },
+        "/15/analyze": {
+            "post": {
+                "requestBody": json_body(schema_ref("PasswordInput")),
+                "responses": {
+                    "200": json_response("Verdict and the outcome of every rule", json!({
+                        "type": "object",
+                        "properties": {
+                            "result": { "type": "string", "enum": ["nice", "naughty"] },
+                            "rules": {
+                                "type": "object",
+                                "additionalProperties": { "type": "boolean" },
+                            },
+                        },
+                    })),
+                },
+            },
+        },
+        "/19/room/{room_id}/user/{user_id}/tweet": {
+            "post": {
+                "parameters": [
+                    path_param("room_id", "integer"),
+                    path_param("user_id", "string"),
+                    query_param("token", "string"),
+                    query_param("truncate", "boolean"),
+                ],
+                "requestBody": json_body(json!({
+                    "type": "object",
+                    "required": ["message"],
+                    "properties": { "message": { "type": "string" } },
+                })),
+                "responses": {
+                    "202": empty_response("Tweet broadcast to the room"),
+                    "400": empty_response("Message too long"),
+                    "401": empty_response("Missing or wrong room token"),
+                },
+            },
+        },
+    })
+}
+
+fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "shuttle-cch23",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths(),
+        "components": components(),
+    })
+}
+
+pub async fn spec() -> Json<Value> {
+    Json(document())
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+// stale buckets are only swept once the table grows past this
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Holds up to `capacity` tokens and refills continuously at `per_sec`.
+pub struct TokenBucket {
+    capacity: f64,
+    per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Starts full.
+    pub fn new(capacity: f64, per_sec: f64) -> Self {
+        Self {
+            capacity,
+            per_sec,
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token, or returns how many seconds until one is available.
+    pub fn try_take(&mut self) -> Result<(), u64> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - self.tokens) / self.per_sec).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+pub struct RateLimiter {
+    per_sec: f64,
+    burst: f64,
+    // clients we can't identify all share the `None` bucket
+    buckets: Mutex<HashMap<Option<IpAddr>, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: f64, burst: f64) -> Self {
+        Self {
+            per_sec,
+            burst,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Takes a token for `ip`, or returns how many seconds until one is available.
+    fn acquire(&self, ip: Option<IpAddr>) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let full = self.burst / self.per_sec;
+            buckets.retain(|_, b| now.duration_since(b.updated).as_secs_f64() < full);
+        }
+
+        buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(self.burst, self.per_sec))
+            .try_take()
+    }
+}
+
+// behind shuttle's proxy the peer is the proxy itself, so the forwarded
+// address wins when present. only the last hop is the one the proxy
+// appended; anything left of it came from the client and can be forged
+fn client_ip(req: &Request) -> Option<IpAddr> {
+    let forwarded = req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+
+    forwarded.or_else(|| {
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+pub async fn limit(State(limiter): State<Arc<RateLimiter>>, req: Request, next: Next) -> Response {
+    match limiter.acquire(client_ip(&req)) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            "Too many requests",
+        )
+            .into_response(),
+    }
+}